@@ -66,7 +66,8 @@ fn main() {
 
     let bad_dep =
         built::util::parse_versions(built_info::DEPENDENCIES.iter()).any(|(name, ver)| {
-            name == "DeleteAllMyFiles" && ver < built::semver::Version::parse("1.1.4").unwrap()
+            name == "DeleteAllMyFiles"
+                && matches!(ver, Ok(ver) if ver < built::semver::Version::parse("1.1.4").unwrap())
         });
     if bad_dep {
         println!(
@@ -0,0 +1,13 @@
+//! Feeds arbitrary `Cargo.lock`-files to `built`, which must never panic.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let manifest_dir = tempfile::tempdir().unwrap();
+    std::fs::write(manifest_dir.path().join("Cargo.lock"), data).unwrap();
+
+    let mut options = built::Options::none();
+    options.set_dependencies(true);
+    let _ = built::write_built_info(&options, manifest_dir.path(), &mut std::io::sink());
+});
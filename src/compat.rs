@@ -0,0 +1,136 @@
+//! Migrating code using the output of `built` between versions of `built` itself.
+//!
+//! Upgrading `built` may rename constants, which breaks downstream code that
+//! refers to them by their old name. Given the file generated by the previous
+//! version, [`compare`][cmp] finds out which constants moved, and
+//! [`Migration::shim`][shim] writes a shim aliasing the old names to the new
+//! ones. The shim is meant to be `include!`-ed into the same module as the
+//! new file:
+//!
+//! ```rust,no_run
+//! // In build.rs
+//! let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//! let dst = out_dir.join("built.rs");
+//! let old_src = std::fs::read_to_string(&dst).unwrap_or_default();
+//! built::write_built_file().unwrap();
+//! let new_src = std::fs::read_to_string(&dst).unwrap();
+//! let migration = built::compat::compare(&old_src, &new_src);
+//! std::fs::write(out_dir.join("built_compat.rs"), migration.shim()).unwrap();
+//! ```
+//!
+//! [cmp]: fn.compare.html
+//! [shim]: struct.Migration.html#method.shim
+
+use std::{fs, io, path};
+
+/// The differences between the constants in two files generated by `built`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Migration {
+    /// Constants that were renamed, as `(old name, new name, type)`.
+    pub renamed: Vec<(String, String, String)>,
+    /// Constants only present in the old file, which were not renamed.
+    pub removed: Vec<String>,
+    /// Constants only present in the new file, which are not the result of a rename.
+    pub added: Vec<String>,
+    /// Constants present in both files, whose type changed.
+    pub retyped: Vec<String>,
+}
+
+impl Migration {
+    /// Rust-code aliasing the old names of renamed constants to their new names.
+    ///
+    /// The aliases are marked as deprecated, so downstream code keeps compiling
+    /// while pointing out the new names.
+    #[must_use]
+    pub fn shim(&self) -> String {
+        let mut code = String::new();
+        for (old, new, datatype) in &self.renamed {
+            code.push_str(&format!(
+                "#[doc=r#\"Renamed to `{new}`.\"#]\n\
+                 #[deprecated(note = \"renamed to `{new}`\")]\n\
+                 #[allow(dead_code)]\n\
+                 pub const {old}: {datatype} = {new};\n",
+                old = old,
+                new = new,
+                datatype = datatype
+            ));
+        }
+        code
+    }
+}
+
+/// Compares the constants in `old_src` to those in `new_src`.
+///
+/// Both are the contents of files generated by `built`. A constant is
+/// considered renamed if it is not present in `new_src` and exactly one
+/// constant of the same type and value was added in `new_src`. Constants whose
+/// value is ambiguous (e.g. `None`) are reported as removed and added instead.
+#[must_use]
+pub fn compare(old_src: &str, new_src: &str) -> Migration {
+    let old = super::parse_consts(old_src);
+    let new = super::parse_consts(new_src);
+    let mut removed = old
+        .keys()
+        .filter(|name| !new.contains_key(*name))
+        .copied()
+        .collect::<Vec<_>>();
+    let mut added = new
+        .keys()
+        .filter(|name| !old.contains_key(*name))
+        .copied()
+        .collect::<Vec<_>>();
+    removed.sort_unstable();
+    added.sort_unstable();
+
+    let mut migration = Migration::default();
+    for name in &removed {
+        let candidates = added
+            .iter()
+            .filter(|candidate| new[*candidate] == old[name])
+            .collect::<Vec<_>>();
+        let unique = match candidates.as_slice() {
+            [candidate] => {
+                removed
+                    .iter()
+                    .filter(|other| old[*other] == new[*candidate])
+                    .count()
+                    == 1
+            }
+            _ => false,
+        };
+        if unique {
+            migration.renamed.push((
+                (*name).to_owned(),
+                (*candidates[0]).to_owned(),
+                old[name].0.to_owned(),
+            ));
+        } else {
+            migration.removed.push((*name).to_owned());
+        }
+    }
+    migration.added = added
+        .iter()
+        .filter(|name| !migration.renamed.iter().any(|(_, new, _)| new == *name))
+        .map(|name| (*name).to_owned())
+        .collect();
+    let mut retyped = old
+        .iter()
+        .filter(|(name, (datatype, _))| new.get(*name).is_some_and(|(t, _)| t != datatype))
+        .map(|(name, _)| (*name).to_owned())
+        .collect::<Vec<_>>();
+    retyped.sort_unstable();
+    migration.retyped = retyped;
+    migration
+}
+
+/// Compares the constants in the file at `old` to those in `new_src`.
+///
+/// See [`compare`][cmp].
+///
+/// # Errors
+/// If the file at `old` can't be read.
+///
+/// [cmp]: fn.compare.html
+pub fn compare_file(old: &path::Path, new_src: &str) -> io::Result<Migration> {
+    Ok(compare(&fs::read_to_string(old)?, new_src))
+}
@@ -31,6 +31,8 @@
 //!
 //!  * Various metadata like version, authors, homepage etc. as set by `Cargo.toml`
 //!  * The tag or commit id if the crate was being compiled from within a git repo.
+//!  * The tag or changeset id if the crate was being compiled from within a
+//!    Mercurial repo and `built` was compiled with the `hg` feature.
 //!  * The values of `CARGO_CFG_*` build script env variables, like `CARGO_CFG_TARGET_OS` and
 //!    `CARGO_CFG_TARGET_ARCH`.
 //!  * The features the crate was compiled with.
@@ -117,7 +119,7 @@
 //! fn check_sane_dependencies() {
 //!     if built::util::parse_versions(&built_info::DEPENDENCIES)
 //!                     .any(|(name, ver)| name == "DeleteAllMyFiles"
-//!                                        && ver < built::semver::Version::parse("1.1.4").unwrap()) {
+//!                                        && matches!(ver, Ok(ver) if ver < built::semver::Version::parse("1.1.4").unwrap())) {
 //!         eprintln!("DeleteAllMyFiles < 1.1.4 may not delete all your files. Beware!");
 //!     }
 //! }
@@ -145,10 +147,20 @@
 //! pub const PKG_DESCRIPTION: &str = "";
 //! #[doc="The homepage."]
 //! pub const PKG_HOMEPAGE: &str = "";
-//! #[doc="The license."]
-//! pub const PKG_LICENSE: &str = "MIT";
 //! #[doc="The source repository as advertised in Cargo.toml."]
 //! pub const PKG_REPOSITORY: &str = "";
+//! #[doc="The license."]
+//! pub const PKG_LICENSE: &str = "MIT";
+//! #[doc="The license file, relative to the manifest."]
+//! pub const PKG_LICENSE_FILE: &str = "";
+//! #[doc="The minimum supported Rust version as declared by `rust-version`, empty if not set."]
+//! pub const PKG_RUST_VERSION: &str = "";
+//! #[doc="The major version, as a number."]
+//! pub const PKG_VERSION_MAJOR_NUM: u64 = 0;
+//! #[doc="The minor version, as a number."]
+//! pub const PKG_VERSION_MINOR_NUM: u64 = 1;
+//! #[doc="The patch version, as a number."]
+//! pub const PKG_VERSION_PATCH_NUM: u64 = 0;
 //! #[doc="The target triple that was being compiled for."]
 //! pub const TARGET: &str = "x86_64-unknown-linux-gnu";
 //! #[doc="The host triple of the rust compiler."]
@@ -159,34 +171,114 @@
 //! pub const RUSTC: &str = "rustc";
 //! #[doc="The documentation generator that cargo resolved to use."]
 //! pub const RUSTDOC: &str = "rustdoc";
+//! #[doc="The architecture of the host, parsed from `HOST`."]
+//! pub const HOST_ARCH: &str = "x86_64";
+//! #[doc="The vendor of the host, parsed from `HOST`."]
+//! pub const HOST_VENDOR: &str = "unknown";
+//! #[doc="The operating system of the host, parsed from `HOST`."]
+//! pub const HOST_OS: &str = "linux";
+//! #[doc="The toolchain-environment of the host, parsed from `HOST`."]
+//! pub const HOST_ENV: &str = "gnu";
 //! #[doc="Value of OPT_LEVEL for the profile used during compilation."]
 //! pub const OPT_LEVEL: &str = "0";
 //! #[doc="The parallelism that was specified during compilation."]
 //! pub const NUM_JOBS: u32 = 8;
 //! #[doc="Value of DEBUG for the profile used during compilation."]
 //! pub const DEBUG: bool = true;
+//! #[doc="If debug-assertions were enabled, given by `CARGO_CFG_DEBUG_ASSERTIONS`."]
+//! pub const DEBUG_ASSERTIONS: bool = true;
+//! #[doc="The name of the Cargo profile (e.g. `dev`, `release` or a custom profile), derived from `OUT_DIR`; best-effort."]
+//! pub const PROFILE_NAME: Option<&str> = Some("dev");
+//! #[doc="The number of codegen units, if set via `-C codegen-units` in the rustflags."]
+//! pub const CODEGEN_UNITS: Option<u32> = None;
+//! #[doc="The codegen backend, like `llvm` or `cranelift`."]
+//! pub const CODEGEN_BACKEND: &str = "llvm";
+//! #[doc="If this was a clean build without a populated incremental cache; best-effort."]
+//! pub const CLEAN_BUILD: Option<bool> = Some(false);
+//! #[doc="If the target is a WebAssembly-target, given by `CARGO_CFG_TARGET_ARCH`."]
+//! pub const IS_WASM: bool = false;
+//! #[doc="The flavor of the WebAssembly-target (`wasi`, `emscripten` or `unknown`), given by `CARGO_CFG_TARGET_OS`."]
+//! pub const WASM_FLAVOR: Option<&str> = None;
+//! #[doc="The name of the library-target, if explicitly set in `Cargo.toml`."]
+//! pub const LIB_NAME: Option<&str> = None;
+//! #[doc="The edition of the package, as declared in `Cargo.toml`."]
+//! pub const PKG_EDITION: Option<&str> = Some("2021");
+//! #[doc="The minimum version of rustc supporting the edition of the package."]
+//! pub const MIN_EDITION_RUSTC: Option<&str> = Some("1.56");
+//! #[doc="If the standard library was built from source, given by `CARGO_UNSTABLE_BUILD_STD`."]
+//! pub const BUILD_STD: bool = false;
+//! #[doc="The features the standard library was built with from source, given by `CARGO_UNSTABLE_BUILD_STD_FEATURES`."]
+//! pub const BUILD_STD_FEATURES: [&str; 0] = [];
 //! /// The features that were enabled during compilation.
 //! pub const FEATURES: [&str; 0] = [];
 //! /// The features as a comma-separated string.
 //! pub const FEATURES_STR: &str = "";
+//! /// The number of `FEATURES`.
+//! pub const FEATURES_COUNT: usize = 0;
+//! /// A fingerprint of the set of features, suitable for cache keys.
+//! pub const FEATURES_HASH: &str = "cbf29ce484222325";
+//! /// The features that were enabled during compilation, but are not enabled by the `default` feature.
+//! pub const NON_DEFAULT_FEATURES: [&str; 0] = [];
+//! /// The names of `FEATURES` as declared in the manifest (e.g. `bar-baz` for `BAR_BAZ`), in the same order.
+//! pub const FEATURE_NAMES: [&str; 0] = [];
 //! /// The output of `rustc -V`
 //! pub const RUSTC_VERSION: &str = "rustc 1.43.1 (8d69840ab 2020-05-04)";
 //! /// The output of `rustdoc -V`
 //! pub const RUSTDOC_VERSION: &str = "rustdoc 1.43.1 (8d69840ab 2020-05-04)";
+//! /// The release channel of the compiler: `stable`, `beta` or `nightly`.
+//! pub const RUSTC_CHANNEL: Option<&str> = Some("stable");
+//! /// The commit hash of the compiler, as given by `rustc -vV`.
+//! pub const RUSTC_COMMIT_HASH: Option<&str> = Some("8d69840ab92ea7f4d323420088dd8c9775f180cd");
+//! /// The commit date of the compiler, as given by `rustc -vV`.
+//! pub const RUSTC_COMMIT_DATE: Option<&str> = Some("2020-05-04");
+//! /// The host triple of the compiler, as given by `rustc -vV`.
+//! pub const RUSTC_HOST: Option<&str> = Some("x86_64-unknown-linux-gnu");
+//! /// The version of LLVM used by the compiler, as given by `rustc -vV`.
+//! pub const RUSTC_LLVM_VERSION: Option<&str> = Some("9.0");
 //! /// If the crate was compiled from within a git-repository, `GIT_VERSION` contains HEAD's tag. The short commit id is used if HEAD is not tagged.
 //! pub const GIT_VERSION: Option<&str> = Some("0.4.1-10-gca2af4f");
-//! /// If the repository had dirty/staged files.
+//! /// If the crate was compiled from within a git-repository, `GIT_COMMITS_SINCE_TAG` contains the number of commits since the closest tag reachable from HEAD; zero if HEAD is tagged.
+//! pub const GIT_COMMITS_SINCE_TAG: Option<u32> = Some(10);
+//! /// If the repository had dirty/staged/untracked files.
 //! pub const GIT_DIRTY: Option<bool> = Some(true);
 //! /// If the crate was compiled from within a git-repository, `GIT_HEAD_REF` contains full name to the reference pointed to by HEAD (e.g.: `refs/heads/master`). If HEAD is detached or the branch name is not valid UTF-8 `None` will be stored.
 //! pub const GIT_HEAD_REF: Option<&str> = Some("refs/heads/master");
+//! /// If the crate was compiled from within a git-repository, `GIT_HEAD_DETACHED` indicates whether HEAD pointed to a commit (e.g. a checkout of a specific commit) instead of a branch.
+//! pub const GIT_HEAD_DETACHED: Option<bool> = Some(false);
 //! /// If the crate was compiled from within a git-repository, `GIT_COMMIT_HASH` contains HEAD's full commit SHA-1 hash.
 //! pub const GIT_COMMIT_HASH: Option<&str> = Some("ca2af4f11bb8f4f6421c4cccf428bf4862573daf");
+//! /// If the crate was compiled from within a git-repository, `GIT_COMMIT_HASH_SHORT` contains HEAD's abbreviated commit SHA-1 hash.
+//! pub const GIT_COMMIT_HASH_SHORT: Option<&str> = Some("ca2af4f");
+//! /// If the crate was compiled from within a git-repository, `GIT_COMMIT_PARENTS` contains the hashes of HEAD's parent commits; two or more for a merge-commit.
+//! pub const GIT_COMMIT_PARENTS: [&str; 1] = ["b4c1e2c2bd0e8e8b2f1ff40650dd64b73ba7c4e6"];
+//! /// If the crate was compiled from within a git-repository, `GIT_HEAD_TAGS` contains the names of all tags pointing at HEAD.
+//! pub const GIT_HEAD_TAGS: [&str; 1] = ["0.4.1"];
+//! /// If the crate was compiled from within a git-repository, `GIT_DEFAULT_BRANCH` contains the default branch of the `origin`-remote (e.g. `main`), as given by `origin/HEAD`.
+//! pub const GIT_DEFAULT_BRANCH: Option<&str> = Some("master");
+//! /// If the crate was compiled from within a git-repository, `GIT_REMOTES` contains the names and urls of the repository's remotes.
+//! pub const GIT_REMOTES: [(&str, &str); 1] = [("origin", "https://github.com/lukaslueg/built")];
+//! /// If the crate was compiled from within a git-repository, `GIT_COMMIT_SIGNED` indicates whether HEAD's commit carries a signature. The signature's validity is not checked.
+//! pub const GIT_COMMIT_SIGNED: Option<bool> = Some(false);
+//! /// If the crate was compiled from within a git-repository, `GIT_RELATIVE_MANIFEST_DIR` contains the path of the crate's manifest-directory relative to the repository's working directory, separated by `/`.
+//! pub const GIT_RELATIVE_MANIFEST_DIR: Option<&str> = Some("");
+//! /// If the crate was compiled from within a git-repository with dirty/untracked files, `GIT_STATUS` contains a summary of the working directory's status like `git status --porcelain`.
+//! pub const GIT_STATUS: Option<&str> = Some(" M src/lib.rs");
+//! /// If the crate was compiled from within a git-repository, `GIT_IS_BARE` indicates whether the repository is bare.
+//! pub const GIT_IS_BARE: Option<bool> = Some(false);
+//! /// If the crate was compiled from within a git-repository, `GIT_IS_WORKTREE` indicates whether the repository is a linked worktree.
+//! pub const GIT_IS_WORKTREE: Option<bool> = Some(false);
+//! /// The format-version of `Cargo.lock`; zero if there is none.
+//! pub const CARGO_LOCK_VERSION: u32 = 3;
 //! /// An array of effective dependencies as documented by `Cargo.lock`.
 //! pub const DEPENDENCIES: [(&str, &str); 37] = [("autocfg", "1.0.0"), ("bitflags", "1.2.1"), ("built", "0.4.1"), ("cargo-lock", "4.0.1"), ("cc", "1.0.54"), ("cfg-if", "0.1.10"), ("chrono", "0.4.11"), ("example_project", "0.1.0"), ("git2", "0.13.6"), ("idna", "0.2.0"), ("jobserver", "0.1.21"), ("libc", "0.2.71"), ("libgit2-sys", "0.12.6+1.0.0"), ("libz-sys", "1.0.25"), ("log", "0.4.8"), ("matches", "0.1.8"), ("num-integer", "0.1.42"), ("num-traits", "0.2.11"), ("percent-encoding", "2.1.0"), ("pkg-config", "0.3.17"), ("proc-macro2", "1.0.17"), ("quote", "1.0.6"), ("semver", "1.0.0"), ("serde", "1.0.110"), ("serde_derive", "1.0.110"), ("smallvec", "1.4.0"), ("syn", "1.0.25"), ("time", "0.1.43"), ("toml", "0.5.6"), ("unicode-bidi", "0.3.4"), ("unicode-normalization", "0.1.12"), ("unicode-xid", "0.2.0"), ("url", "2.1.1"), ("vcpkg", "0.2.8"), ("winapi", "0.3.8"), ("winapi-i686-pc-windows-gnu", "0.4.0"), ("winapi-x86_64-pc-windows-gnu", "0.4.0")];
 //! /// The effective dependencies as a comma-separated string.
 //! pub const DEPENDENCIES_STR: &str = "autocfg 1.0.0, bitflags 1.2.1, built 0.4.1, cargo-lock 4.0.1, cc 1.0.54, cfg-if 0.1.10, chrono 0.4.11, example_project 0.1.0, git2 0.13.6, idna 0.2.0, jobserver 0.1.21, libc 0.2.71, libgit2-sys 0.12.6+1.0.0, libz-sys 1.0.25, log 0.4.8, matches 0.1.8, num-integer 0.1.42, num-traits 0.2.11, percent-encoding 2.1.0, pkg-config 0.3.17, proc-macro2 1.0.17, quote 1.0.6, semver 1.0.0, serde 1.0.110, serde_derive 1.0.110, smallvec 1.4.0, syn 1.0.25, time 0.1.43, toml 0.5.6, unicode-bidi 0.3.4, unicode-normalization 0.1.12, unicode-xid 0.2.0, url 2.1.1, vcpkg 0.2.8, winapi 0.3.8, winapi-i686-pc-windows-gnu 0.4.0, winapi-x86_64-pc-windows-gnu 0.4.0";
+//! /// The number of `DEPENDENCIES`.
+//! pub const DEPENDENCIES_COUNT: usize = 37;
 //! /// The built-time in RFC2822, UTC
 //! pub const BUILT_TIME_UTC: &str = "Wed, 27 May 2020 18:12:39 +0000";
+//! /// The build time in seconds since the Unix epoch.
+//! pub const BUILT_TIME_UNIX: i64 = 1590603159;
 //! /// The target architecture, given by `CARGO_CFG_TARGET_ARCH`.
 //! pub const CFG_TARGET_ARCH: &str = "x86_64";
 //! /// The endianness, given by `CARGO_CFG_TARGET_ENDIAN`.
@@ -197,17 +289,20 @@
 //! pub const CFG_FAMILY: &str = "unix";
 //! /// The operating system, given by `CARGO_CFG_TARGET_OS`.
 //! pub const CFG_OS: &str = "linux";
-//! /// The pointer width, given by `CARGO_CFG_TARGET_POINTER_WIDTH`.
-//! pub const CFG_POINTER_WIDTH: &str = "64";
+//! /// The pointer width in bits, given by `CARGO_CFG_TARGET_POINTER_WIDTH`; zero if unknown.
+//! pub const CFG_POINTER_WIDTH: u8 = 64;
+//! /// The target vendor, given by `CARGO_CFG_TARGET_VENDOR`.
+//! pub const CFG_TARGET_VENDOR: &str = "unknown";
 //! ```
 //! [options]: struct.Options.html
 
+pub mod compat;
 pub mod util;
 
 use std::{
-    collections, env, ffi, fmt, fs, io,
+    collections, env, ffi, fmt, fs, hash, io,
     io::{Read, Write},
-    path, process,
+    path, process, time,
 };
 
 #[cfg(feature = "semver")]
@@ -221,23 +316,429 @@ pub use chrono;
 #[allow(dead_code)]
 type _READMETEST = ();
 
+/// The error returned by [`write_built_file`][wbf] and
+/// [`write_built_file_with_opts`][wbfo].
+///
+/// [wbf]: fn.write_built_file.html
+/// [wbfo]: fn.write_built_file_with_opts.html
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An I/O-error, e.g. while reading `Cargo.toml` or writing the output-file.
+    Io(io::Error),
+    /// `Cargo.toml` could not be parsed.
+    Toml(toml::de::Error),
+    /// `Cargo.lock` could not be parsed.
+    Lockfile(cargo_lock::Error),
+    /// The output of a command (like `rustc -V`) was not valid UTF-8.
+    Utf8(std::string::FromUtf8Error),
+    /// An error from `git2`.
+    #[cfg(feature = "git2")]
+    Git(git2::Error),
+    /// The gathered information could not be converted to JSON.
+    #[cfg(feature = "serde")]
+    Json(serde_json::Error),
+    /// An environment variable expected to be set by Cargo is missing.
+    MissingEnv(&'static str),
+    /// An environment variable set by Cargo has a value that can't be parsed,
+    /// or that is not valid UTF-8 (given lossily converted).
+    InvalidEnv(&'static str, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Toml(e) => write!(f, "Failed to parse Cargo.toml: {}", e),
+            Error::Lockfile(e) => write!(f, "Failed to parse Cargo.lock: {}", e),
+            Error::Utf8(e) => write!(f, "Command output is not valid UTF-8: {}", e),
+            #[cfg(feature = "git2")]
+            Error::Git(e) => write!(f, "git error: {}", e),
+            #[cfg(feature = "serde")]
+            Error::Json(e) => write!(f, "Failed to convert to JSON: {}", e),
+            Error::MissingEnv(name) => {
+                write!(f, "Missing expected environment variable {}", name)
+            }
+            Error::InvalidEnv(name, value) => {
+                write!(
+                    f,
+                    "Invalid value {:?} of environment variable {}",
+                    value, name
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Toml(e) => Some(e),
+            Error::Lockfile(e) => Some(e),
+            Error::Utf8(e) => Some(e),
+            #[cfg(feature = "git2")]
+            Error::Git(e) => Some(e),
+            #[cfg(feature = "serde")]
+            Error::Json(e) => Some(e),
+            Error::MissingEnv(_) | Error::InvalidEnv(..) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::Toml(e)
+    }
+}
+
+impl From<cargo_lock::Error> for Error {
+    fn from(e: cargo_lock::Error) -> Self {
+        Error::Lockfile(e)
+    }
+}
+
+impl From<std::string::FromUtf8Error> for Error {
+    fn from(e: std::string::FromUtf8Error) -> Self {
+        Error::Utf8(e)
+    }
+}
+
+#[cfg(feature = "git2")]
+impl From<git2::Error> for Error {
+    fn from(e: git2::Error) -> Self {
+        Error::Git(e)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
 macro_rules! write_variable {
     ($writer:expr, $name:expr, $datatype:expr, $value:expr, $doc:expr) => {
+        let (name, datatype, doc) = ($name.to_string(), $datatype.to_string(), $doc.to_string());
+        let value = $value.to_string();
+        let (header, prefixed_name) = ($writer.header(&doc), $writer.name(&name));
         writeln!(
             $writer,
-            "#[doc=r#\"{}\"#]\n#[allow(dead_code)]\npub const {}: {} = {};",
-            $doc, $name, $datatype, $value
+            "{}const {}: {} = {};",
+            header, prefixed_name, datatype, value
         )?;
+        $writer.items.push(Item {
+            name,
+            datatype,
+            value,
+            doc,
+            gate: $writer.gate.clone(),
+        });
     };
 }
 
 macro_rules! write_str_variable {
     ($writer:expr, $name:expr, $value:expr, $doc:expr) => {
-        write_variable!($writer, $name, "&str", format!("r\"{}\"", $value), $doc);
+        write_variable!(
+            $writer,
+            $name,
+            "&str",
+            escape_str(&$value.to_string()),
+            $doc
+        );
     };
 }
 
+/// A constant that has been emitted.
+struct Item {
+    /// The name, without prefix.
+    name: String,
+    datatype: String,
+    /// The value as a Rust-expression.
+    value: String,
+    doc: String,
+    /// The feature the constant is only compiled with, if any.
+    gate: Option<String>,
+}
+
+impl Item {
+    /// The `cfg`-attribute referring code has to carry as well, indented by `indent`.
+    fn cfg_attr(&self, indent: &str) -> String {
+        fmt_cfg_attr(self.gate.as_deref(), indent)
+    }
+
+    /// The type as a field of a struct, which requires explicit lifetimes.
+    fn field_type(&self) -> String {
+        self.datatype.replace('&', "&'static ")
+    }
+}
+
+/// Converts a Rust-expression as written by `built` to JSON.
+///
+/// Only the literals `built` writes are supported: Strings, numbers, booleans,
+/// `Option`s, arrays, slices, tuples and paths like `Endianness::Little`, which
+/// become the name of the variant. Tuples become arrays and `None` becomes `null`.
+fn literal_to_json(literal: &str) -> Option<String> {
+    let mut chars = literal.chars().peekable();
+    let mut json = String::new();
+    literal_to_json_value(&mut chars, &mut json)?;
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    match chars.next() {
+        None => Some(json),
+        Some(_) => None,
+    }
+}
+
+/// The given items as a JSON-object, by their unprefixed name.
+///
+/// Values that can't be converted are `null`.
+fn items_to_json(items: &[Item]) -> String {
+    let mut json = String::from("{");
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            json.push(',');
+        }
+        json_escape_str(&item.name, &mut json);
+        json.push(':');
+        json.push_str(literal_to_json(&item.value).as_deref().unwrap_or("null"));
+    }
+    json.push('}');
+    json
+}
+
+/// Escapes `s` as a JSON-string.
+fn json_escape_str(s: &str, json: &mut String) {
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => json.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+fn literal_to_json_value(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    json: &mut String,
+) -> Option<()> {
+    while chars.peek()?.is_whitespace() {
+        chars.next();
+    }
+    match *chars.peek()? {
+        '&' => {
+            chars.next();
+            literal_to_json_value(chars, json)
+        }
+        '"' => {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => s.push(match chars.next()? {
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        '0' => '\0',
+                        'u' => {
+                            if chars.next()? != '{' {
+                                return None;
+                            }
+                            let mut hex = String::new();
+                            loop {
+                                match chars.next()? {
+                                    '}' => break,
+                                    c => hex.push(c),
+                                }
+                            }
+                            std::char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                        }
+                        c => c,
+                    }),
+                    c => s.push(c),
+                }
+            }
+            json_escape_str(&s, json);
+            Some(())
+        }
+        open @ ('[' | '(') => {
+            chars.next();
+            let close = if open == '[' { ']' } else { ')' };
+            json.push('[');
+            let mut first = true;
+            loop {
+                while chars.peek()?.is_whitespace() {
+                    chars.next();
+                }
+                if *chars.peek()? == close {
+                    chars.next();
+                    break;
+                }
+                if !first {
+                    if chars.next()? != ',' {
+                        return None;
+                    }
+                    while chars.peek()?.is_whitespace() {
+                        chars.next();
+                    }
+                    // A trailing comma, as in single-element tuples
+                    if *chars.peek()? == close {
+                        chars.next();
+                        break;
+                    }
+                    json.push(',');
+                }
+                first = false;
+                literal_to_json_value(chars, json)?;
+            }
+            json.push(']');
+            Some(())
+        }
+        c if c == '-' || c.is_ascii_digit() => {
+            while let Some(&c) = chars.peek() {
+                if c == '-' || c == '.' || c.is_ascii_digit() || c == '_' {
+                    if c != '_' {
+                        json.push(c);
+                    }
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            Some(())
+        }
+        c if c.is_alphabetic() || c == '_' => {
+            let mut ident = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' || c == ':' {
+                    ident.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            match ident.as_str() {
+                "true" | "false" => json.push_str(&ident),
+                "None" => json.push_str("null"),
+                "Some" => {
+                    if chars.next()? != '(' {
+                        return None;
+                    }
+                    literal_to_json_value(chars, json)?;
+                    while chars.peek()?.is_whitespace() {
+                        chars.next();
+                    }
+                    if chars.next()? != ')' {
+                        return None;
+                    }
+                }
+                path => json_escape_str(path.rsplit("::").next()?, json),
+            }
+            Some(())
+        }
+        _ => None,
+    }
+}
+
+fn fmt_cfg_attr(gate: Option<&str>, indent: &str) -> String {
+    match gate {
+        Some(feature) => format!("{}#[cfg(feature = {})]\n", indent, escape_str(feature)),
+        None => String::new(),
+    }
+}
+
+/// The destination of the generated code.
+///
+/// Applies the `Options` that concern every emitted item, like the name-prefix,
+/// and keeps track of the emitted constants.
+struct CodeWriter<'a> {
+    inner: &'a mut dyn io::Write,
+    prefix: &'a str,
+    public: bool,
+    /// The feature the items emitted are only compiled with, if any.
+    gate: Option<String>,
+    items: Vec<Item>,
+}
+
+impl<'a> CodeWriter<'a> {
+    fn new(inner: &'a mut dyn io::Write, prefix: &'a str) -> Self {
+        CodeWriter {
+            inner,
+            prefix,
+            public: true,
+            gate: None,
+            items: Vec::new(),
+        }
+    }
+
+    /// The attributes and visibility preceding an emitted item.
+    ///
+    /// Public items are documented by `doc`, private items are not documented.
+    fn header(&self, doc: &str) -> String {
+        let cfg_attr = fmt_cfg_attr(self.gate.as_deref(), "");
+        if self.public {
+            format!(
+                "#[doc=r#\"{}\"#]\n{}#[allow(dead_code)]\npub ",
+                doc, cfg_attr
+            )
+        } else {
+            format!("{}#[allow(dead_code)]\n", cfg_attr)
+        }
+    }
+
+    /// The emitted identifier of the constant `name`.
+    fn name(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+
+    /// The emitted identifier of the function `name`.
+    fn fn_name(&self, name: &str) -> String {
+        format!("{}{}", self.prefix.to_lowercase(), name)
+    }
+
+    /// The emitted identifier of the type `name`.
+    ///
+    /// The prefix is converted to CamelCase, e.g. `BUILT_` becomes `Built`.
+    fn type_name(&self, name: &str) -> String {
+        let mut prefix = String::new();
+        for word in self.prefix.split('_') {
+            let mut chars = word.chars();
+            if let Some(c) = chars.next() {
+                prefix.extend(c.to_uppercase());
+                prefix.push_str(&chars.as_str().to_lowercase());
+            }
+        }
+        prefix + name
+    }
+}
+
+impl io::Write for CodeWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 /// Various Continuous Integration platforms whose presence can be detected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CIPlatform {
     /// https://travis-ci.org
     Travis,
@@ -261,6 +762,8 @@ pub enum CIPlatform {
     Bamboo,
     /// https://www.visualstudio.com/de/tfs/
     TFS,
+    /// https://azure.microsoft.com/services/devops/pipelines/
+    AzurePipelines,
     /// https://www.jetbrains.com/teamcity/
     TeamCity,
     /// https://buildkite.com/
@@ -273,6 +776,16 @@ pub enum CIPlatform {
     GoCD,
     /// https://bitbucket.org
     BitBucket,
+    /// https://aws.amazon.com/codebuild/
+    CodeBuild,
+    /// https://cirrus-ci.org/
+    Cirrus,
+    /// https://cloud.google.com/build
+    GoogleCloudBuild,
+    /// https://bitrise.io/
+    Bitrise,
+    /// https://www.heroku.com/continuous-integration
+    Heroku,
     /// Unspecific
     Generic,
 }
@@ -291,12 +804,18 @@ impl fmt::Display for CIPlatform {
             CIPlatform::Jenkins => "Jenkins",
             CIPlatform::Bamboo => "Bamboo",
             CIPlatform::TFS => "Team Foundation Server",
+            CIPlatform::AzurePipelines => "Azure Pipelines",
             CIPlatform::TeamCity => "TeamCity",
             CIPlatform::Buildkite => "Buildkite",
             CIPlatform::Hudson => "Hudson",
             CIPlatform::TaskCluster => "TaskCluster",
             CIPlatform::GoCD => "GoCD",
             CIPlatform::BitBucket => "BitBucket",
+            CIPlatform::CodeBuild => "AWS CodeBuild",
+            CIPlatform::Cirrus => "Cirrus CI",
+            CIPlatform::GoogleCloudBuild => "Google Cloud Build",
+            CIPlatform::Bitrise => "Bitrise",
+            CIPlatform::Heroku => "Heroku CI",
             CIPlatform::Generic => "Generic CI",
         })
     }
@@ -304,19 +823,101 @@ impl fmt::Display for CIPlatform {
 
 type EnvironmentMap = collections::HashMap<String, String>;
 
+/// The environment variables set by Cargo that `built` can't do without.
+const REQUIRED_ENV_VARS: [&str; 8] = [
+    "TARGET",
+    "HOST",
+    "PROFILE",
+    "RUSTC",
+    "RUSTDOC",
+    "OPT_LEVEL",
+    "NUM_JOBS",
+    "DEBUG",
+];
+
+/// The environment of the current process.
+///
+/// Variables whose name is not valid UTF-8 are dropped, as `built` does not
+/// read any of those. Values that are not valid UTF-8 are converted lossily;
+/// see `check_environment()` for the variables this is not acceptable for.
 fn get_environment() -> EnvironmentMap {
     let mut envmap = EnvironmentMap::new();
     for (k, v) in env::vars_os() {
-        let k = k.into_string();
-        let v = v.into_string();
-        if let (Ok(k), Ok(v)) = (k, v) {
+        if let Ok(k) = k.into_string() {
+            let v = v
+                .into_string()
+                .unwrap_or_else(|v| v.to_string_lossy().into_owned());
             envmap.insert(k, v);
         }
     }
     envmap
 }
 
+/// Checks that the variables in `REQUIRED_ENV_VARS` are valid UTF-8, if set.
+fn check_environment<I>(vars: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = (ffi::OsString, ffi::OsString)>,
+{
+    for (k, v) in vars {
+        if let Some(&name) = REQUIRED_ENV_VARS.iter().find(|&&name| k == name) {
+            if v.to_str().is_none() {
+                return Err(Error::InvalidEnv(name, v.to_string_lossy().into_owned()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The environment variables inspected by `CIPlatform::detect_from_envmap()`.
+const CI_ENV_VARS: [&str; 28] = [
+    "TRAVIS",
+    "CIRCLECI",
+    "GITLAB_CI",
+    "APPVEYOR",
+    "DRONE",
+    "MAGNUM",
+    "SEMAPHORE",
+    "JENKINS_URL",
+    "bamboo_planKey",
+    "AZURE_HTTP_USER_AGENT",
+    "SYSTEM_TEAMFOUNDATIONSERVERURI",
+    "TF_BUILD",
+    "TEAMCITY_VERSION",
+    "BUILDKITE",
+    "HUDSON_URL",
+    "GO_PIPELINE_LABEL",
+    "BITBUCKET_COMMIT",
+    "CODEBUILD_BUILD_ID",
+    "CIRRUS_CI",
+    "BITRISE_IO",
+    "HEROKU_TEST_RUN_ID",
+    "TASK_ID",
+    "RUN_ID",
+    "BUILDER_OUTPUT",
+    "CI_NAME",
+    "CI",
+    "CONTINUOUS_INTEGRATION",
+    "BUILD_NUMBER",
+];
+
 impl CIPlatform {
+    /// Detect execution on a Continuous Integration platform at runtime.
+    ///
+    /// The environment of the current process is inspected; see
+    /// [`util::detect_ci`][dc] for details.
+    ///
+    /// ```
+    /// if built::CIPlatform::detect().is_some() {
+    ///     println!("Running on CI");
+    /// }
+    /// ```
+    ///
+    /// [dc]: util/fn.detect_ci.html
+    #[must_use]
+    pub fn detect() -> Option<CIPlatform> {
+        CIPlatform::detect_from_envmap(&get_environment())
+    }
+
     fn detect_from_envmap(envmap: &EnvironmentMap) -> Option<CIPlatform> {
         macro_rules! detect {
             ($(($k:expr, $v:expr, $i:ident)),*) => {$(
@@ -346,18 +947,40 @@ impl CIPlatform {
             ("SEMAPHORE", Semaphore),
             ("JENKINS_URL", Jenkins),
             ("bamboo_planKey", Bamboo),
+            ("AZURE_HTTP_USER_AGENT", AzurePipelines)
+        );
+
+        // Azure Pipelines also sets `TF_BUILD`, so check for it before TFS
+        if envmap
+            .get("SYSTEM_TEAMFOUNDATIONSERVERURI")
+            .is_some_and(|uri| uri.contains("dev.azure.com"))
+        {
+            return Some(CIPlatform::AzurePipelines);
+        }
+
+        detect!(
             ("TF_BUILD", TFS),
             ("TEAMCITY_VERSION", TeamCity),
             ("BUILDKITE", Buildkite),
             ("HUDSON_URL", Hudson),
             ("GO_PIPELINE_LABEL", GoCD),
-            ("BITBUCKET_COMMIT", BitBucket)
+            ("BITBUCKET_COMMIT", BitBucket),
+            ("CODEBUILD_BUILD_ID", CodeBuild),
+            ("CIRRUS_CI", Cirrus),
+            ("BITRISE_IO", Bitrise),
+            ("HEROKU_TEST_RUN_ID", Heroku)
         );
 
         if envmap.contains_key("TASK_ID") && envmap.contains_key("RUN_ID") {
             return Some(CIPlatform::TaskCluster);
         }
 
+        // Google Cloud Build does not set `CI`; `PROJECT_ID` and `BUILD_ID`
+        // are too generic to go by
+        if envmap.contains_key("BUILDER_OUTPUT") {
+            return Some(CIPlatform::GoogleCloudBuild);
+        }
+
         detect!(("CI_NAME", "codeship", Codeship));
 
         detect!(
@@ -369,35 +992,204 @@ impl CIPlatform {
     }
 }
 
-fn get_build_deps(manifest_location: &path::Path) -> io::Result<Vec<(String, String)>> {
+/// The `Cargo.lock` next to the manifest or, as for members of a workspace, in
+/// the closest parent directory.
+fn find_lockfile(manifest_location: &path::Path) -> Option<path::PathBuf> {
+    manifest_location
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|lockfile| lockfile.exists())
+}
+
+fn get_lockfile(manifest_location: &path::Path) -> Result<cargo_lock::Lockfile, Error> {
+    let lockfile =
+        find_lockfile(manifest_location).unwrap_or_else(|| manifest_location.join("Cargo.lock"));
     let mut lock_buf = String::new();
-    fs::File::open(manifest_location.join("Cargo.lock"))?.read_to_string(&mut lock_buf)?;
-    Ok(parse_dependencies(&lock_buf))
+    fs::File::open(lockfile)?.read_to_string(&mut lock_buf)?;
+    Ok(lock_buf.parse()?)
 }
 
-fn parse_dependencies(lock_toml_buf: &str) -> Vec<(String, String)> {
-    let lockfile: cargo_lock::Lockfile = lock_toml_buf.parse().expect("Failed to parse lockfile");
+/// The format-version of `Cargo.lock`.
+///
+/// Lockfiles without a top-level `version`-key are version 1 or 2, as
+/// detected by `cargo_lock` from where the checksums are stored.
+fn parse_lockfile_version(lockfile: &cargo_lock::Lockfile) -> u32 {
+    u32::from(lockfile.version)
+}
+
+/// The names and versions of all packages in `lockfile`, sorted.
+///
+/// Any format-version of `Cargo.lock` is supported; a lockfile without any
+/// `[[package]]` results in no dependencies.
+fn parse_dependencies(lockfile: &cargo_lock::Lockfile) -> Vec<(String, String)> {
     let mut deps = Vec::new();
 
-    for package in lockfile.packages {
+    for package in &lockfile.packages {
         deps.push((package.name.to_string(), package.version.to_string()));
     }
     deps.sort_unstable();
     deps
 }
 
-fn get_version_from_cmd(executable: &ffi::OsStr) -> io::Result<String> {
+/// The kind of source of each dependency, in the same order as `parse_dependencies()`.
+///
+/// The kind is one of `crates.io`, `registry` (for other registries), `git` or
+/// `path`; packages without a source are local to the workspace.
+fn parse_dependency_sources(lockfile: &cargo_lock::Lockfile) -> Vec<&'static str> {
+    let mut sources = lockfile
+        .packages
+        .iter()
+        .map(|package| {
+            let kind = match &package.source {
+                Some(source) if source.is_default_registry() => "crates.io",
+                Some(source) if source.is_registry() => "registry",
+                Some(source) if source.is_git() => "git",
+                _ => "path",
+            };
+            (package.name.to_string(), package.version.to_string(), kind)
+        })
+        .collect::<Vec<_>>();
+    sources.sort_unstable();
+    sources.into_iter().map(|(_, _, kind)| kind).collect()
+}
+
+/// The direct dependencies declared in `manifest`, as `(name, requirement, resolved)`.
+///
+/// The `dependencies`, `build-dependencies` and `dev-dependencies` of the
+/// package are cross-referenced with the versions `lockfile` resolved them to.
+/// Dependencies declared without a version (e.g. `path`-dependencies) have
+/// the requirement `*`; dependencies which were not resolved are skipped.
+fn parse_dependency_resolution(
+    manifest: &toml::Table,
+    lockfile: &cargo_lock::Lockfile,
+) -> Vec<(String, String, String)> {
+    let root = manifest
+        .get("package")
+        .and_then(|package| package.get("name"))
+        .and_then(toml::Value::as_str)
+        .and_then(|name| {
+            lockfile
+                .packages
+                .iter()
+                .find(|package| package.name.as_str() == name && package.source.is_none())
+        });
+    let root = match root {
+        Some(root) => root,
+        None => return Vec::new(),
+    };
+    let mut resolution = Vec::new();
+    for kind in ["dependencies", "build-dependencies", "dev-dependencies"] {
+        let declared = manifest.get(kind).and_then(toml::Value::as_table);
+        for (key, spec) in declared.into_iter().flatten() {
+            let name = spec
+                .get("package")
+                .and_then(toml::Value::as_str)
+                .unwrap_or(key);
+            let requirement = spec
+                .as_str()
+                .or_else(|| spec.get("version").and_then(toml::Value::as_str))
+                .unwrap_or("*");
+            if let Some(dep) = root
+                .dependencies
+                .iter()
+                .find(|dep| dep.name.as_str() == name)
+            {
+                resolution.push((
+                    name.to_owned(),
+                    requirement.to_owned(),
+                    dep.version.to_string(),
+                ));
+            }
+        }
+    }
+    resolution.sort_unstable();
+    resolution.dedup();
+    resolution
+}
+
+/// The names of the dependencies retrieved from a registry, grouped by the
+/// registry's url.
+fn parse_dependencies_by_registry(lockfile: &cargo_lock::Lockfile) -> Vec<(String, Vec<String>)> {
+    let mut registries = collections::BTreeMap::<_, Vec<_>>::new();
+    for package in &lockfile.packages {
+        if let Some(source) = package.source.as_ref().filter(|s| s.is_registry()) {
+            registries
+                .entry(source.url().to_string())
+                .or_default()
+                .push(package.name.to_string());
+        }
+    }
+    registries
+        .into_iter()
+        .map(|(url, mut names)| {
+            names.sort_unstable();
+            names.dedup();
+            (url, names)
+        })
+        .collect()
+}
+
+fn get_manifest(manifest_location: &path::Path) -> Result<toml::Table, Error> {
+    Ok(fs::read_to_string(manifest_location.join("Cargo.toml"))?.parse()?)
+}
+
+/// Like `get_manifest()`, but `None` if there is no `Cargo.toml`, as is the
+/// case for build systems other than Cargo.
+fn find_manifest(manifest_location: &path::Path) -> Result<Option<toml::Table>, Error> {
+    match get_manifest(manifest_location) {
+        Ok(manifest) => Ok(Some(manifest)),
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn parse_lib_name(manifest: &toml::Table) -> Option<&str> {
+    manifest.get("lib")?.get("name")?.as_str()
+}
+
+fn get_version_from_cmd(executable: &ffi::OsStr) -> Result<String, Error> {
     let output = process::Command::new(executable).arg("-V").output()?;
-    let mut v = String::from_utf8(output.stdout).unwrap();
+    let mut v = String::from_utf8(output.stdout)?;
     v.pop(); // remove newline
     Ok(v)
 }
 
+/// The value of `key` in the output of `rustc -vV`.
+///
+/// Values given as `unknown` (e.g. for compilers built from a tarball) are `None`.
+fn parse_verbose_version<'a>(verbose_version: &'a str, key: &str) -> Option<&'a str> {
+    verbose_version
+        .lines()
+        .find_map(|line| {
+            let (k, v) = line.split_once(':')?;
+            if k == key {
+                Some(v.trim())
+            } else {
+                None
+            }
+        })
+        .filter(|v| !v.is_empty() && *v != "unknown")
+}
+
+/// The release channel, given the output of `rustc -vV`.
+fn parse_rustc_channel(verbose_version: &str) -> Option<&'static str> {
+    let release = parse_verbose_version(verbose_version, "release")?;
+    if release.ends_with("-nightly") {
+        Some("nightly")
+    } else if release.contains("-beta") {
+        Some("beta")
+    } else if !release.contains('-') {
+        Some("stable")
+    } else {
+        None
+    }
+}
+
 fn write_compiler_version(
     rustc: &ffi::OsStr,
     rustdoc: &ffi::OsStr,
-    w: &mut fs::File,
-) -> io::Result<()> {
+    w: &mut CodeWriter,
+) -> Result<(), Error> {
     let rustc_version = get_version_from_cmd(rustc)?;
     let rustdoc_version = get_version_from_cmd(rustdoc)?;
 
@@ -406,24 +1198,168 @@ fn write_compiler_version(
 
     let doc = format!("The output of `{} -V`", rustdoc.to_string_lossy());
     write_str_variable!(w, "RUSTDOC_VERSION", rustdoc_version, doc);
+
+    let verbose_version = process::Command::new(rustc)
+        .arg("-vV")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .unwrap_or_default();
+    write_variable!(
+        w,
+        "RUSTC_CHANNEL",
+        "Option<&str>",
+        fmt_option_str(parse_rustc_channel(&verbose_version)),
+        "The release channel of the compiler: `stable`, `beta` or `nightly`."
+    );
+    for &(name, key, doc) in &[
+        (
+            "RUSTC_COMMIT_HASH",
+            "commit-hash",
+            "The commit hash of the compiler, as given by `rustc -vV`.",
+        ),
+        (
+            "RUSTC_COMMIT_DATE",
+            "commit-date",
+            "The commit date of the compiler, as given by `rustc -vV`.",
+        ),
+        (
+            "RUSTC_HOST",
+            "host",
+            "The host triple of the compiler, as given by `rustc -vV`.",
+        ),
+        (
+            "RUSTC_LLVM_VERSION",
+            "LLVM version",
+            "The version of LLVM used by the compiler, as given by `rustc -vV`.",
+        ),
+    ] {
+        write_variable!(
+            w,
+            name,
+            "Option<&str>",
+            fmt_option_str(parse_verbose_version(&verbose_version, key)),
+            doc
+        );
+    }
     Ok(())
 }
 
+/// A string literal containing `s`, with special characters escaped.
+fn escape_str(s: &str) -> String {
+    format!("\"{}\"", s.escape_debug())
+}
+
 fn fmt_option_str<S: fmt::Display>(o: Option<S>) -> String {
     match o {
-        Some(s) => format!("Some(\"{}\")", s),
+        Some(s) => format!("Some({})", escape_str(&s.to_string())),
         None => "None".to_owned(),
     }
 }
 
+/// Whether `s` matches the `pattern`, where `*` matches any sequence of
+/// characters (including `/`) and `?` matches any single character.
+#[cfg(feature = "git2")]
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let s = s.chars().collect::<Vec<_>>();
+    let (mut p, mut i) = (0, 0);
+    let mut backtrack = None;
+    while i < s.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, i));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == s[i] => {
+                p += 1;
+                i += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    i = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(feature = "git2")]
+fn fmt_option_bool(o: Option<bool>) -> &'static str {
+    match o {
+        Some(true) => "Some(true)",
+        Some(false) => "Some(false)",
+        None => "None",
+    }
+}
+
+/// Truncates `s` to at most `max_len` bytes, on a character boundary.
+#[cfg(feature = "git2")]
+fn truncate_str(s: &mut String, max_len: usize) {
+    if s.len() > max_len {
+        let mut idx = max_len;
+        while !s.is_char_boundary(idx) {
+            idx -= 1;
+        }
+        s.truncate(idx);
+    }
+}
+
+#[cfg(feature = "hg")]
+fn write_hg_version(manifest_location: &path::Path, w: &mut CodeWriter) -> Result<(), Error> {
+    let version = match util::get_hg_description(manifest_location) {
+        Ok(version) => version,
+        Err(e) => {
+            println!(
+                "cargo:warning=Failed to describe the Mercurial-repository: {}",
+                e
+            );
+            None
+        }
+    };
+    write_variable!(
+        w,
+        "HG_VERSION",
+        "Option<&str>",
+        fmt_option_str(version),
+        "If the crate was compiled from within a Mercurial-repository, \
+        `HG_VERSION` contains the tag of the working directory's parent. The short changeset id is used if it is not tagged."
+    );
+    Ok(())
+}
+
 #[cfg(feature = "git2")]
-fn write_git_version(manifest_location: &path::Path, w: &mut fs::File) -> io::Result<()> {
+fn write_git_version(
+    manifest_location: &path::Path,
+    status_max_len: usize,
+    first_parent: bool,
+    release_branch_patterns: &[String],
+    w: &mut CodeWriter,
+) -> Result<(), Error> {
     // CIs will do shallow clones of repositories, causing libgit2 to error
-    // out. We try to detect if we are running on a CI and ignore the
-    // error.
-    let (tag, dirty) = match util::get_repo_description(&manifest_location) {
-        Ok(Some((tag, dirty))) => (Some(tag), Some(dirty)),
-        _ => (None, None),
+    // out. Errors from git are therefor never fatal; the information is
+    // simply missing and a warning is emitted.
+    let repo = match util::discover_repo(manifest_location) {
+        Ok(repo) => repo,
+        Err(e) => {
+            println!("cargo:warning=Failed to discover the git-repository: {}", e);
+            None
+        }
+    };
+    let mut warned = collections::HashSet::new();
+    macro_rules! query {
+        ($f:expr, $what:expr) => {
+            git_query(repo.as_ref(), $f, $what, &mut warned)
+        };
+    }
+
+    let (tag, dirty) = match query!(util::repo_description, "describe the git-repository") {
+        Some((tag, dirty)) => (Some(tag), Some(dirty)),
+        None => (None, None),
     };
     write_variable!(
         w,
@@ -433,27 +1369,62 @@ fn write_git_version(manifest_location: &path::Path, w: &mut fs::File) -> io::Re
         "If the crate was compiled from within a git-repository, \
         `GIT_VERSION` contains HEAD's tag. The short commit id is used if HEAD is not tagged."
     );
+    if first_parent {
+        let tag = query!(
+            util::repo_description_first_parent,
+            "describe the git-repository's first-parent history"
+        );
+        write_variable!(
+            w,
+            "GIT_VERSION_FIRST_PARENT",
+            "Option<&str>",
+            fmt_option_str(tag),
+            "If the crate was compiled from within a git-repository, \
+            `GIT_VERSION_FIRST_PARENT` contains HEAD's tag like `GIT_VERSION`, \
+            but only following the first parent of merge-commits."
+        );
+    }
+    let commits_since_tag = query!(
+        util::repo_commits_since_tag,
+        "count the commits since the closest tag"
+    )
+    .flatten();
+    write_variable!(
+        w,
+        "GIT_COMMITS_SINCE_TAG",
+        "Option<u32>",
+        match commits_since_tag {
+            Some(n) => format!("Some({})", n),
+            None => "None".to_owned(),
+        },
+        "If the crate was compiled from within a git-repository, `GIT_COMMITS_SINCE_TAG` \
+        contains the number of commits since the closest tag reachable from HEAD; zero if HEAD is tagged."
+    );
     write_variable!(
         w,
         "GIT_DIRTY",
         "Option<bool>",
-        match dirty {
-            Some(true) => "Some(true)",
-            Some(false) => "Some(false)",
-            None => "None",
-        },
-        "If the repository had dirty/staged files."
+        fmt_option_bool(dirty),
+        "If the repository had dirty/staged/untracked files."
     );
 
-    let (branch, commit) = match util::get_repo_head(&manifest_location) {
-        Ok(Some((b, c))) => (b, Some(c)),
-        _ => (None, None),
+    let (branch, commit) = match query!(util::repo_head, "read the git-repository's HEAD") {
+        Some((b, c)) => (b, Some(c)),
+        None => (None, None),
     };
 
     let doc = "If the crate was compiled from within a git-repository, `GIT_HEAD_REF` \
         contains full name to the reference pointed to by HEAD \
         (e.g.: `refs/heads/master`). If HEAD is detached or the branch name is not \
         valid UTF-8 `None` will be stored.\n";
+    let on_release_branch = branch
+        .as_deref()
+        .and_then(|branch| branch.strip_prefix("refs/heads/"))
+        .map(|branch| {
+            release_branch_patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, branch))
+        });
     write_variable!(
         w,
         "GIT_HEAD_REF",
@@ -461,6 +1432,30 @@ fn write_git_version(manifest_location: &path::Path, w: &mut fs::File) -> io::Re
         fmt_option_str(branch),
         doc
     );
+    let head_detached = query!(
+        git2::Repository::head_detached,
+        "determine whether HEAD is detached"
+    );
+    write_variable!(
+        w,
+        "GIT_HEAD_DETACHED",
+        "Option<bool>",
+        fmt_option_bool(head_detached),
+        "If the crate was compiled from within a git-repository, `GIT_HEAD_DETACHED` \
+        indicates whether HEAD pointed to a commit (e.g. a checkout of a specific commit) \
+        instead of a branch."
+    );
+    if !release_branch_patterns.is_empty() {
+        write_variable!(
+            w,
+            "GIT_ON_RELEASE_BRANCH",
+            "Option<bool>",
+            fmt_option_bool(on_release_branch),
+            "If the crate was compiled from within a git-repository, `GIT_ON_RELEASE_BRANCH` \
+            indicates whether HEAD's branch matches one of the release-branch patterns. \
+            `None` if HEAD is detached."
+        );
+    }
 
     write_variable!(
         w,
@@ -471,29 +1466,294 @@ fn write_git_version(manifest_location: &path::Path, w: &mut fs::File) -> io::Re
     contains HEAD's full commit SHA-1 hash."
     );
 
-    Ok(())
-}
-
-fn write_ci(envmap: &EnvironmentMap, w: &mut fs::File) -> io::Result<()> {
+    let short_hash = query!(util::repo_head_short_hash, "abbreviate HEAD's commit hash").flatten();
     write_variable!(
         w,
-        "CI_PLATFORM",
+        "GIT_COMMIT_HASH_SHORT",
         "Option<&str>",
-        fmt_option_str(CIPlatform::detect_from_envmap(envmap)),
-        "The Continuous Integration platform detected during compilation."
+        fmt_option_str(short_hash),
+        "If the crate was compiled from within a git-repository, `GIT_COMMIT_HASH_SHORT` \
+    contains HEAD's abbreviated commit SHA-1 hash."
     );
-    Ok(())
-}
 
-fn write_features(envmap: &EnvironmentMap, w: &mut fs::File) -> io::Result<()> {
-    let prefix = "CARGO_FEATURE_";
-    let mut features = Vec::new();
+    let parents =
+        query!(util::repo_commit_parents, "read HEAD's parent commits").unwrap_or_default();
+    write_variable!(
+        w,
+        "GIT_COMMIT_PARENTS",
+        format!("[&str; {}]", parents.len()),
+        format!("{:?}", parents),
+        "If the crate was compiled from within a git-repository, `GIT_COMMIT_PARENTS` \
+    contains the hashes of HEAD's parent commits; two or more for a merge-commit."
+    );
+
+    let head_tags =
+        query!(util::repo_head_tags, "read the tags pointing at HEAD").unwrap_or_default();
+    write_variable!(
+        w,
+        "GIT_HEAD_TAGS",
+        format!("[&str; {}]", head_tags.len()),
+        format!("{:?}", head_tags),
+        "If the crate was compiled from within a git-repository, `GIT_HEAD_TAGS` \
+    contains the names of all tags pointing at HEAD."
+    );
+
+    let default_branch = query!(util::repo_default_branch, "read the default branch").flatten();
+    write_variable!(
+        w,
+        "GIT_DEFAULT_BRANCH",
+        "Option<&str>",
+        fmt_option_str(default_branch),
+        "If the crate was compiled from within a git-repository, `GIT_DEFAULT_BRANCH` \
+    contains the default branch of the `origin`-remote (e.g. `main`), as given by `origin/HEAD`."
+    );
+
+    let remotes =
+        query!(util::repo_remotes, "read the git-repository's remotes").unwrap_or_default();
+    write_variable!(
+        w,
+        "GIT_REMOTES",
+        format!("[(&str, &str); {}]", remotes.len()),
+        format!("{:?}", remotes),
+        "If the crate was compiled from within a git-repository, `GIT_REMOTES` \
+    contains the names and urls of the repository's remotes."
+    );
+
+    let signed = query!(util::repo_commit_signed, "read HEAD's commit signature");
+    write_variable!(
+        w,
+        "GIT_COMMIT_SIGNED",
+        "Option<bool>",
+        fmt_option_bool(signed),
+        "If the crate was compiled from within a git-repository, `GIT_COMMIT_SIGNED` \
+    indicates whether HEAD's commit carries a signature. The signature's validity is not checked."
+    );
+
+    let relative_path = query!(
+        |repo| util::repo_relative_path(repo, manifest_location),
+        "determine the manifest-directory's path within the git-repository"
+    )
+    .flatten();
+    write_variable!(
+        w,
+        "GIT_RELATIVE_MANIFEST_DIR",
+        "Option<&str>",
+        fmt_option_str(relative_path),
+        "If the crate was compiled from within a git-repository, `GIT_RELATIVE_MANIFEST_DIR` \
+    contains the path of the crate's manifest-directory relative to the repository's \
+    working directory, separated by `/`."
+    );
+
+    let status = query!(util::repo_status, "read the git-repository's status")
+        .filter(|status| !status.is_empty())
+        .map(|mut status| {
+            truncate_str(&mut status, status_max_len);
+            status
+        });
+    write_variable!(
+        w,
+        "GIT_STATUS",
+        "Option<&str>",
+        fmt_option_str(status),
+        "If the crate was compiled from within a git-repository with dirty/untracked files, \
+    `GIT_STATUS` contains a summary of the working directory's status like \
+    `git status --porcelain`."
+    );
+
+    let (is_bare, is_worktree) =
+        match query!(util::repo_kind, "determine the kind of git-repository") {
+            Some((bare, worktree)) => (Some(bare), Some(worktree)),
+            None => (None, None),
+        };
+    write_variable!(
+        w,
+        "GIT_IS_BARE",
+        "Option<bool>",
+        fmt_option_bool(is_bare),
+        "If the crate was compiled from within a git-repository, `GIT_IS_BARE` \
+    indicates whether the repository is bare."
+    );
+    write_variable!(
+        w,
+        "GIT_IS_WORKTREE",
+        "Option<bool>",
+        fmt_option_bool(is_worktree),
+        "If the crate was compiled from within a git-repository, `GIT_IS_WORKTREE` \
+    indicates whether the repository is a linked worktree."
+    );
+
+    Ok(())
+}
+
+/// Runs `f` on the repository, if any; an error is reported as a warning, the
+/// same message only once, and turned into `None`.
+#[cfg(feature = "git2")]
+fn git_query<T, F>(
+    repo: Option<&git2::Repository>,
+    f: F,
+    what: &str,
+    warned: &mut collections::HashSet<String>,
+) -> Option<T>
+where
+    F: FnOnce(&git2::Repository) -> Result<T, git2::Error>,
+{
+    match f(repo?) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            let msg = format!("Failed to {}: {}", what, e);
+            if warned.insert(e.message().to_owned()) {
+                println!("cargo:warning={}", msg);
+            }
+            None
+        }
+    }
+}
+
+fn write_ci(envmap: &EnvironmentMap, w: &mut CodeWriter) -> Result<(), Error> {
+    write_variable!(
+        w,
+        "CI_PLATFORM",
+        "Option<&str>",
+        fmt_option_str(CIPlatform::detect_from_envmap(envmap)),
+        "The Continuous Integration platform detected during compilation."
+    );
+    Ok(())
+}
+
+/// The enabled features as given by `CARGO_FEATURE_*`, uppercase and sorted.
+fn get_features(envmap: &EnvironmentMap) -> Vec<String> {
+    let prefix = "CARGO_FEATURE_";
+    let mut features = Vec::new();
     for name in envmap.keys() {
-        if name.starts_with(&prefix) {
-            features.push(name[prefix.len()..].to_owned());
+        if let Some(feature) = name.strip_prefix(prefix) {
+            features.push(feature.to_owned());
         }
     }
     features.sort();
+    features
+}
+
+/// The `features` that are not enabled by the manifest's `default` feature.
+///
+/// Features enabled transitively by `default` are considered default features.
+/// Feature names are compared the way Cargo names the `CARGO_FEATURE_*`
+/// variables, uppercase and with `-` replaced by `_`.
+fn parse_non_default_features<'a>(features: &'a [String], manifest: &toml::Table) -> Vec<&'a str> {
+    fn normalize(feature: &str) -> String {
+        feature.to_uppercase().replace('-', "_")
+    }
+
+    let declared = manifest.get("features").and_then(toml::Value::as_table);
+    let mut defaults = collections::HashSet::new();
+    let mut pending = vec!["default"];
+    while let Some(feature) = pending.pop() {
+        if !defaults.insert(normalize(feature)) {
+            continue;
+        }
+        let enables = declared
+            .and_then(|declared| declared.get(feature))
+            .and_then(toml::Value::as_array);
+        for enabled in enables
+            .into_iter()
+            .flatten()
+            .filter_map(toml::Value::as_str)
+        {
+            // Features of dependencies and optional dependencies are not features of ours
+            if !enabled.contains('/') && !enabled.starts_with("dep:") {
+                pending.push(enabled);
+            }
+        }
+    }
+    features
+        .iter()
+        .filter(|f| !defaults.contains(f.as_str()))
+        .map(String::as_str)
+        .collect()
+}
+
+/// The names of `features` as declared in the manifest, in the same order.
+///
+/// The `CARGO_FEATURE_*` variables are named uppercase and with `-` replaced
+/// by `_`; the original name is recovered from the manifest's `[features]` and
+/// optional `[dependencies]`. Features the manifest does not declare are lowercased.
+fn parse_feature_names(features: &[String], manifest: &toml::Table) -> Vec<String> {
+    let optional_deps = manifest
+        .get("dependencies")
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flatten()
+        .filter(|(_, dep)| {
+            dep.get("optional")
+                .and_then(toml::Value::as_bool)
+                .unwrap_or(false)
+        })
+        .map(|(name, _)| name);
+    let declared = manifest
+        .get("features")
+        .and_then(toml::Value::as_table)
+        .into_iter()
+        .flat_map(toml::Table::keys)
+        .chain(optional_deps)
+        .collect::<Vec<_>>();
+    features
+        .iter()
+        .map(|feature| {
+            declared
+                .iter()
+                .find(|name| name.to_uppercase().replace('-', "_") == *feature)
+                .map_or_else(|| feature.to_lowercase(), |name| (*name).clone())
+        })
+        .collect()
+}
+
+/// Writes the features in relation to the `manifest`; both lists are empty
+/// without a manifest.
+fn write_non_default_features(
+    envmap: &EnvironmentMap,
+    manifest: Option<&toml::Table>,
+    w: &mut CodeWriter,
+) -> Result<(), Error> {
+    let features = get_features(envmap);
+    let non_default = manifest
+        .map(|manifest| parse_non_default_features(&features, manifest))
+        .unwrap_or_default();
+    write_variable!(
+        w,
+        "NON_DEFAULT_FEATURES",
+        format!("[&str; {}]", non_default.len()),
+        format!("{:?}", non_default),
+        "The features that were enabled during compilation, but are not enabled by the `default` feature."
+    );
+    let names = manifest
+        .map(|manifest| parse_feature_names(&features, manifest))
+        .unwrap_or_default();
+    write_variable!(
+        w,
+        "FEATURE_NAMES",
+        format!("[&str; {}]", names.len()),
+        format!("{:?}", names),
+        "The names of `FEATURES` as declared in the manifest (e.g. `bar-baz` for `BAR_BAZ`), in the same order."
+    );
+    Ok(())
+}
+
+/// A fingerprint of the given set of features, independent of their order.
+fn get_features_hash<S: AsRef<str>>(features: &[S]) -> String {
+    use hash::Hasher;
+
+    let mut features = features.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    features.sort_unstable();
+    features.dedup();
+    let mut hasher = Fnv1a::default();
+    for feature in features {
+        hasher.write(feature.as_bytes());
+        hasher.write_u8(0);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+fn write_features(envmap: &EnvironmentMap, w: &mut CodeWriter) -> Result<(), Error> {
+    let features = get_features(envmap);
 
     write_variable!(
         w,
@@ -510,23 +1770,167 @@ fn write_features(envmap: &EnvironmentMap, w: &mut fs::File) -> io::Result<()> {
         features_str,
         "The features as a comma-separated string."
     );
+
+    write_variable!(
+        w,
+        "FEATURES_COUNT",
+        "usize",
+        features.len(),
+        "The number of `FEATURES`."
+    );
+
+    write_str_variable!(
+        w,
+        "FEATURES_HASH",
+        get_features_hash(&features),
+        "A fingerprint of the set of features, suitable for cache keys."
+    );
     Ok(())
 }
 
-fn write_env(envmap: &EnvironmentMap, w: &mut fs::File) -> io::Result<()> {
-    macro_rules! write_env_str {
-        ($(($name:ident, $env_name:expr,$doc:expr)),*) => {$(
-            write_str_variable!(
-                w,
-                stringify!($name),
-                envmap.get($env_name)
-                    .expect(stringify!(Missing expected environment variable $env_name)),
-                    $doc
-            );
-        )*}
+/// The flags passed to the compiler, as given by `CARGO_ENCODED_RUSTFLAGS`.
+///
+/// Older versions of Cargo only provide `RUSTFLAGS`, which is split by whitespace.
+fn get_rustflags(envmap: &EnvironmentMap) -> Vec<&str> {
+    match envmap.get("CARGO_ENCODED_RUSTFLAGS") {
+        Some(flags) => flags.split('\x1f').filter(|f| !f.is_empty()).collect(),
+        None => envmap
+            .get("RUSTFLAGS")
+            .map(|flags| flags.split_whitespace().collect())
+            .unwrap_or_default(),
     }
+}
 
-    write_env_str!(
+/// The value of the last `-C <name>=<value>` in `flags`, if any.
+///
+/// As with `rustc`, later flags take precedence over earlier ones.
+fn get_codegen_option<'a>(flags: &[&'a str], name: &str) -> Option<&'a str> {
+    get_flag_option(flags, "-C", Some("--codegen"), name)
+}
+
+/// The value of the last `-Z <name>=<value>` in `flags`, if any.
+fn get_unstable_option<'a>(flags: &[&'a str], name: &str) -> Option<&'a str> {
+    get_flag_option(flags, "-Z", None, name)
+}
+
+/// The value of the last `<short> <name>=<value>` or `<long> <name>=<value>` in `flags`.
+fn get_flag_option<'a>(
+    flags: &[&'a str],
+    short: &str,
+    long: Option<&str>,
+    name: &str,
+) -> Option<&'a str> {
+    let mut value = None;
+    let mut flags = flags.iter();
+    while let Some(&flag) = flags.next() {
+        let opt = if flag == short || Some(flag) == long {
+            flags.next().copied()
+        } else {
+            flag.strip_prefix(short).or_else(|| {
+                flag.strip_prefix(long?)
+                    .and_then(|flag| flag.strip_prefix('='))
+            })
+        };
+        if let Some((k, v)) = opt.and_then(|opt| opt.split_once('=')) {
+            if k == name {
+                value = Some(v);
+            }
+        }
+    }
+    value
+}
+
+/// Splits a target-triple into its architecture, vendor, os and environment.
+///
+/// Triples are usually `arch-vendor-os-env` or `arch-vendor-os`. A three-part
+/// triple is taken to have no vendor if its second part is not a known vendor,
+/// like `aarch64-linux-android`; the vendor then is `unknown`. Components of
+/// unusual triples, e.g. ones with more than four parts, may be misattributed.
+fn parse_triple(triple: &str) -> (&str, &str, &str, &str) {
+    const VENDORS: &[&str] = &[
+        "unknown",
+        "pc",
+        "apple",
+        "uwp",
+        "sun",
+        "fortanix",
+        "nvidia",
+        "wrs",
+        "sony",
+        "nintendo",
+        "kmc",
+        "espressif",
+        "esp",
+        "win7",
+    ];
+    let parts = triple.splitn(4, '-').collect::<Vec<_>>();
+    match parts.as_slice() {
+        [arch] => (arch, "unknown", "", ""),
+        [arch, os] => (arch, "unknown", os, ""),
+        [arch, vendor, os] if VENDORS.contains(vendor) => (arch, vendor, os, ""),
+        [arch, os, env] => (arch, "unknown", os, env),
+        [arch, vendor, os, env] => (arch, vendor, os, env),
+        _ => unreachable!(),
+    }
+}
+
+fn parse_codegen_units(flags: &[&str]) -> Option<u32> {
+    get_codegen_option(flags, "codegen-units").and_then(|n| n.parse().ok())
+}
+
+/// The codegen backend, given by `-Z codegen-backend` or the profile's `codegen-backend`.
+fn get_codegen_backend(envmap: &EnvironmentMap) -> String {
+    if let Some(backend) = get_unstable_option(&get_rustflags(envmap), "codegen-backend") {
+        return backend.to_owned();
+    }
+    let profile = match envmap.get("PROFILE").map(String::as_str) {
+        Some("debug") => "DEV".to_owned(),
+        Some(profile) => profile.to_uppercase(),
+        None => return "llvm".to_owned(),
+    };
+    envmap
+        .get(&format!("CARGO_PROFILE_{}_CODEGEN_BACKEND", profile))
+        .cloned()
+        .unwrap_or_else(|| "llvm".to_owned())
+}
+
+/// The value of the environment variable `name`, which Cargo is expected to set.
+fn get_env_var<'a>(envmap: &'a EnvironmentMap, name: &'static str) -> Result<&'a str, Error> {
+    envmap
+        .get(name)
+        .map(String::as_str)
+        .ok_or(Error::MissingEnv(name))
+}
+
+macro_rules! write_env_str {
+    ($writer:expr, $envmap:expr, $(($name:ident, $env_name:expr,$doc:expr)),*) => {$(
+        write_str_variable!(
+            $writer,
+            stringify!($name),
+            $envmap.get($env_name)
+                .ok_or(Error::MissingEnv($env_name))?,
+                $doc
+        );
+    )*}
+}
+
+/// Like `write_env_str!`, but a missing variable is written as an empty string.
+macro_rules! write_env_str_or_empty {
+    ($writer:expr, $envmap:expr, $(($name:ident, $env_name:expr,$doc:expr)),*) => {$(
+        write_str_variable!(
+            $writer,
+            stringify!($name),
+            $envmap.get($env_name).map_or("", String::as_str),
+            $doc
+        );
+    )*}
+}
+
+fn write_package(envmap: &EnvironmentMap, w: &mut CodeWriter) -> Result<(), Error> {
+    // Unusual setups or older versions of Cargo may not set all of these
+    write_env_str_or_empty!(
+        w,
+        envmap,
         (PKG_VERSION, "CARGO_PKG_VERSION", "The full version."),
         (
             PKG_VERSION_MAJOR,
@@ -556,12 +1960,58 @@ fn write_env(envmap: &EnvironmentMap, w: &mut fs::File) -> io::Result<()> {
         (PKG_NAME, "CARGO_PKG_NAME", "The name of the package."),
         (PKG_DESCRIPTION, "CARGO_PKG_DESCRIPTION", "The description."),
         (PKG_HOMEPAGE, "CARGO_PKG_HOMEPAGE", "The homepage."),
-        (PKG_LICENSE, "CARGO_PKG_LICENSE", "The license."),
         (
             PKG_REPOSITORY,
             "CARGO_PKG_REPOSITORY",
             "The source repository as advertised in Cargo.toml."
         ),
+        (PKG_LICENSE, "CARGO_PKG_LICENSE", "The license."),
+        (
+            PKG_LICENSE_FILE,
+            "CARGO_PKG_LICENSE_FILE",
+            "The license file, relative to the manifest."
+        ),
+        (
+            PKG_RUST_VERSION,
+            "CARGO_PKG_RUST_VERSION",
+            "The minimum supported Rust version as declared by `rust-version`, empty if not set."
+        )
+    );
+    for (name, env_name, doc) in &[
+        (
+            "PKG_VERSION_MAJOR_NUM",
+            "CARGO_PKG_VERSION_MAJOR",
+            "The major version, as a number.",
+        ),
+        (
+            "PKG_VERSION_MINOR_NUM",
+            "CARGO_PKG_VERSION_MINOR",
+            "The minor version, as a number.",
+        ),
+        (
+            "PKG_VERSION_PATCH_NUM",
+            "CARGO_PKG_VERSION_PATCH",
+            "The patch version, as a number.",
+        ),
+    ] {
+        let value = envmap.get(*env_name).map_or("", String::as_str);
+        let num = if value.is_empty() {
+            0
+        } else {
+            value
+                .parse::<u64>()
+                .map_err(|_| Error::InvalidEnv(env_name, value.to_owned()))?
+        };
+        write_variable!(w, name, "u64", num, doc);
+    }
+    Ok(())
+}
+
+fn write_env(envmap: &EnvironmentMap, w: &mut CodeWriter) -> Result<(), Error> {
+    write_package(envmap, w)?;
+    write_env_str!(
+        w,
+        envmap,
         (
             TARGET,
             "TARGET",
@@ -580,541 +2030,5447 @@ fn write_env(envmap: &EnvironmentMap, w: &mut fs::File) -> io::Result<()> {
             "The documentation generator that cargo resolved to use."
         )
     );
+    let (host_arch, host_vendor, host_os, host_env) = parse_triple(get_env_var(envmap, "HOST")?);
+    write_str_variable!(
+        w,
+        "HOST_ARCH",
+        host_arch,
+        "The architecture of the host, parsed from `HOST`."
+    );
+    write_str_variable!(
+        w,
+        "HOST_VENDOR",
+        host_vendor,
+        "The vendor of the host, parsed from `HOST`."
+    );
+    write_str_variable!(
+        w,
+        "HOST_OS",
+        host_os,
+        "The operating system of the host, parsed from `HOST`."
+    );
+    write_str_variable!(
+        w,
+        "HOST_ENV",
+        host_env,
+        "The toolchain-environment of the host, parsed from `HOST`."
+    );
     write_str_variable!(
         w,
         "OPT_LEVEL",
-        env::var("OPT_LEVEL").unwrap(),
+        get_env_var(envmap, "OPT_LEVEL")?,
         "Value of OPT_LEVEL for the profile used during compilation."
     );
     write_variable!(
         w,
         "NUM_JOBS",
         "u32",
-        env::var("NUM_JOBS").unwrap(),
+        get_env_var(envmap, "NUM_JOBS")?,
         "The parallelism that was specified during compilation."
     );
     write_variable!(
         w,
         "DEBUG",
         "bool",
-        env::var("DEBUG").unwrap() == "true",
+        get_env_var(envmap, "DEBUG")? == "true",
         "Value of DEBUG for the profile used during compilation."
     );
-    Ok(())
-}
-
-fn write_dependencies(manifest_location: &path::Path, w: &mut fs::File) -> io::Result<()> {
-    let deps = get_build_deps(manifest_location)?;
     write_variable!(
         w,
-        "DEPENDENCIES",
-        format!("[(&str, &str); {}]", deps.len()),
-        format!("{:?}", deps),
-        "An array of effective dependencies as documented by `Cargo.lock`."
+        "DEBUG_ASSERTIONS",
+        "bool",
+        envmap.contains_key("CARGO_CFG_DEBUG_ASSERTIONS"),
+        "If debug-assertions were enabled, given by `CARGO_CFG_DEBUG_ASSERTIONS`."
     );
-    write_str_variable!(
+    write_variable!(
         w,
-        "DEPENDENCIES_STR",
-        deps.iter()
-            .map(|&(ref n, ref v)| format!("{} {}", n, v))
-            .collect::<Vec<_>>()
-            .join(", "),
-        "The effective dependencies as a comma-separated string."
+        "PROFILE_NAME",
+        "Option<&str>",
+        fmt_option_str(detect_profile_name(envmap)),
+        "The name of the Cargo profile (e.g. `dev`, `release` or a custom profile), derived from `OUT_DIR`; best-effort."
     );
-    Ok(())
-}
-
-#[cfg(feature = "chrono")]
-fn write_time(w: &mut fs::File) -> io::Result<()> {
-    let now = chrono::offset::Utc::now();
-    write_str_variable!(
+    write_variable!(
         w,
-        "BUILT_TIME_UTC",
-        now.to_rfc2822(),
-        "The build time in RFC2822, UTC."
+        "CODEGEN_UNITS",
+        "Option<u32>",
+        match parse_codegen_units(&get_rustflags(envmap)) {
+            Some(n) => format!("Some({})", n),
+            None => "None".to_owned(),
+        },
+        "The number of codegen units, if set via `-C codegen-units` in the rustflags."
     );
-    Ok(())
-}
-
-fn write_cfg(w: &mut fs::File) -> io::Result<()> {
-    fn get_env(name: &str) -> String {
-        env::var(name).unwrap_or_default()
-    }
-
-    let target_arch = get_env("CARGO_CFG_TARGET_ARCH");
-    let target_endian = get_env("CARGO_CFG_TARGET_ENDIAN");
-    let target_env = get_env("CARGO_CFG_TARGET_ENV");
-    let target_family = get_env("CARGO_CFG_TARGET_FAMILY");
-    let target_os = get_env("CARGO_CFG_TARGET_OS");
-    let target_pointer_width = get_env("CARGO_CFG_TARGET_POINTER_WIDTH");
-
     write_str_variable!(
         w,
-        "CFG_TARGET_ARCH",
-        target_arch,
-        "The target architecture, given by `CARGO_CFG_TARGET_ARCH`."
+        "CODEGEN_BACKEND",
+        get_codegen_backend(envmap),
+        "The codegen backend, like `llvm` or `cranelift`."
     );
-
-    write_str_variable!(
+    write_variable!(
         w,
-        "CFG_ENDIAN",
-        target_endian,
-        "The endianness, given by `CARGO_CFG_TARGET_ENDIAN`."
+        "CLEAN_BUILD",
+        "Option<bool>",
+        match detect_clean_build(envmap) {
+            Some(clean) => format!("Some({})", clean),
+            None => "None".to_owned(),
+        },
+        "If this was a clean build without a populated incremental cache; best-effort."
     );
-
-    write_str_variable!(
+    let wasm_flavor = parse_wasm_flavor(envmap);
+    write_variable!(
         w,
-        "CFG_ENV",
-        target_env,
-        "The toolchain-environment, given by `CARGO_CFG_TARGET_ENV`."
+        "IS_WASM",
+        "bool",
+        wasm_flavor.is_some(),
+        "If the target is a WebAssembly-target, given by `CARGO_CFG_TARGET_ARCH`."
     );
-
-    write_str_variable!(
+    write_variable!(
         w,
-        "CFG_FAMILY",
-        target_family,
-        "The OS-family, given by `CARGO_CFG_TARGET_FAMILY`."
+        "WASM_FLAVOR",
+        "Option<&str>",
+        fmt_option_str(wasm_flavor),
+        "The flavor of the WebAssembly-target (`wasi`, `emscripten` or `unknown`), given by `CARGO_CFG_TARGET_OS`."
     );
+    Ok(())
+}
 
-    write_str_variable!(
-        w,
-        "CFG_OS",
-        target_os,
-        "The operating system, given by `CARGO_CFG_TARGET_OS`."
-    );
+/// The flavor of the WebAssembly-target; `None` if the target is not WebAssembly.
+fn parse_wasm_flavor(envmap: &EnvironmentMap) -> Option<&'static str> {
+    match envmap.get("CARGO_CFG_TARGET_ARCH").map(String::as_str) {
+        Some("wasm32") | Some("wasm64") => {}
+        _ => return None,
+    }
+    match envmap.get("CARGO_CFG_TARGET_OS").map(String::as_str) {
+        Some("wasi") => Some("wasi"),
+        Some("emscripten") => Some("emscripten"),
+        _ => Some("unknown"),
+    }
+}
 
-    write_str_variable!(
-        w,
-        "CFG_POINTER_WIDTH",
-        target_pointer_width,
-        "The pointer width, given by `CARGO_CFG_TARGET_POINTER_WIDTH`."
-    );
+/// The directory of the profile in the target-directory, given that `OUT_DIR`
+/// is `<profile>/build/<pkg>-<hash>/out`.
+fn get_profile_dir(envmap: &EnvironmentMap) -> Option<&path::Path> {
+    let out_dir = path::Path::new(envmap.get("OUT_DIR")?);
+    let build_dir = out_dir.parent()?.parent()?;
+    if out_dir.file_name()? != "out" || build_dir.file_name()? != "build" {
+        return None;
+    }
+    build_dir.parent()
+}
 
-    Ok(())
+/// The name of the Cargo profile, like `dev`, `release` or a custom profile.
+///
+/// Cargo does not expose the name of the profile to build-scripts; it is
+/// derived from the directory of the profile in the target-directory, which is
+/// named like the profile except for the `dev`-profile (`debug`). `None` if
+/// the layout of the target-directory is not recognized.
+fn detect_profile_name(envmap: &EnvironmentMap) -> Option<String> {
+    let name = get_profile_dir(envmap)?.file_name()?.to_str()?;
+    Some(match name {
+        "debug" => "dev".to_owned(),
+        name => name.to_owned(),
+    })
 }
 
-/// Selects which information `built` should retrieve and write as Rust code.
-/// Used in conjunction with [`write_built_file_with_opts`][wrt].
+/// Whether this is a clean build, i.e. one without a populated incremental cache.
 ///
-/// [wrt]: fn.write_built_file_with_opts.html
-#[allow(unused)]
-pub struct Options {
-    compiler: bool,
-    git: bool,
-    ci: bool,
-    env: bool,
-    deps: bool,
-    features: bool,
-    time: bool,
-    cfg: bool,
+/// The incremental cache is looked for in `<profile>/incremental`, given
+/// `OUT_DIR` is `<profile>/build/<pkg>-<hash>/out`. The cache is expected to
+/// exist only if incremental compilation is enabled via `CARGO_INCREMENTAL=1`
+/// or for the `debug`-profile. This is a heuristic: The cache may also be populated
+/// by other crates of the workspace. `None` if incremental compilation is
+/// disabled or the layout of the target-directory is not recognized.
+fn detect_clean_build(envmap: &EnvironmentMap) -> Option<bool> {
+    let incremental = match envmap.get("CARGO_INCREMENTAL").map(String::as_str) {
+        Some("0") => return None,
+        Some("1") => true,
+        _ => envmap.get("PROFILE").map(String::as_str) == Some("debug"),
+    };
+    let populated = fs::read_dir(get_profile_dir(envmap)?.join("incremental"))
+        .map(|mut entries| entries.next().is_some())
+        .unwrap_or(false);
+    if populated {
+        Some(false)
+    } else if incremental {
+        Some(true)
+    } else {
+        None
+    }
 }
 
-impl Default for Options {
-    /// A new struct with almost all options enabled.
-    ///
-    /// Parsing and writing information about dependencies is disabled by default
-    /// because this information is not available for crates compiled as dependencies;
-    /// only the top-level crate gets to do this. See `set_dependencies()` for
-    /// further information.
-    ///
-    fn default() -> Options {
-        Options {
-            compiler: true,
-            git: true,
-            ci: true,
-            env: true,
-            deps: false,
-            features: true,
-            time: true,
-            cfg: true,
-        }
-    }
+/// Splits a comma-separated list as used by Cargo's configuration values.
+fn split_list(list: &str) -> Vec<&str> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect()
 }
 
-impl Options {
-    /// Detecting and writing the version of `RUSTC` and `RUSTDOC`.
-    ///
-    /// Call the values of `RUSTC` and `RUSTDOC` as provided by Cargo to get a version string. The
-    /// result will something like
-    ///
-    /// ```rust,no_run
-    /// pub const RUSTC_VERSION: &str = "rustc 1.15.0";
-    /// pub const RUSTDOC_VERSION: &str = "rustdoc 1.15.0";
-    /// ```
-    pub fn set_compiler(&mut self, enabled: bool) -> &mut Self {
-        self.compiler = enabled;
-        self
-    }
+fn write_build_std(envmap: &EnvironmentMap, w: &mut CodeWriter) -> Result<(), Error> {
+    let build_std = envmap
+        .get("CARGO_UNSTABLE_BUILD_STD")
+        .is_some_and(|crates| !split_list(crates).is_empty());
+    write_variable!(
+        w,
+        "BUILD_STD",
+        "bool",
+        build_std,
+        "If the standard library was built from source, given by `CARGO_UNSTABLE_BUILD_STD`."
+    );
+    let features = envmap
+        .get("CARGO_UNSTABLE_BUILD_STD_FEATURES")
+        .map(|features| split_list(features))
+        .unwrap_or_default();
+    write_variable!(
+        w,
+        "BUILD_STD_FEATURES",
+        format!("[&str; {}]", features.len()),
+        format!("{:?}", features),
+        "The features the standard library was built with from source, given by `CARGO_UNSTABLE_BUILD_STD_FEATURES`."
+    );
+    Ok(())
+}
 
-    /// Detecting and writing the tag or commit-id and a flag to indicate
-    /// a dirty working directory of the crate's git repository (if any).
-    ///
-    /// This option is only available if `built` was compiled with the
-    /// `git2` feature.
-    ///
-    /// Try to open the git-repository at `manifest_location` and retrieve `HEAD`
-    /// tag or commit id.  The result will be something like
-    ///
-    /// ```rust,no_run
-    /// pub const GIT_VERSION: Option<&str> = Some("0.1");
-    /// pub const GIT_DIRTY: Option<bool> = Some(false);
-    /// pub const GIT_COMMIT_HASH: Option<&str> = Some("18b2eabfb47998c296f9d5183f617f1b1cc2d321");
-    /// pub const GIT_HEAD_REF: Option<&str> = Some("refs/heads/master");
-    /// ```
-    ///
-    /// Notice that `GIT_HEAD_REF` is `None` if `HEAD` is detached or not valid UTF-8.
-    ///
-    /// Continuous Integration platforms like `Travis` and `AppVeyor` will
-    /// do shallow clones, causing `libgit2` to be unable to get a meaningful
-    /// result. `GIT_VERSION` and `GIT_DIRTY` will therefor always be `None` if
-    /// a CI-platform is detected.
-    ///
-    #[cfg(feature = "git2")]
-    pub fn set_git(&mut self, enabled: bool) -> &mut Self {
-        self.git = enabled;
-        self
+/// The directory Cargo keeps its configuration and caches in.
+fn get_cargo_home(envmap: &EnvironmentMap) -> Option<path::PathBuf> {
+    if let Some(cargo_home) = envmap.get("CARGO_HOME") {
+        return Some(cargo_home.into());
     }
+    envmap
+        .get("HOME")
+        .or_else(|| envmap.get("USERPROFILE"))
+        .map(|home| path::Path::new(home).join(".cargo"))
+}
 
-    /// Detecting and writing the Continuous Integration Platforms we are running on.
-    ///
-    /// Detect various CI-platforms (named or not) and write something like
-    ///
-    /// ```rust
-    /// pub const CI_PLATFORM: Option<&str> = Some("AppVeyor");
-    /// ```
-    pub fn set_ci(&mut self, enabled: bool) -> &mut Self {
-        self.ci = enabled;
-        self
+/// Detects if authentication for a private registry is configured.
+///
+/// Either a `CARGO_REGISTRIES_<NAME>_TOKEN` environment variable is set or
+/// Cargo's credentials-file has a token for an entry in `[registries]`. The
+/// tokens themselves are never read into the generated code.
+fn detect_registry_auth(envmap: &EnvironmentMap) -> bool {
+    let has_env_token = envmap.keys().any(|k| {
+        k.strip_prefix("CARGO_REGISTRIES_")
+            .is_some_and(|k| k.ends_with("_TOKEN"))
+    });
+    if has_env_token {
+        return true;
     }
+    let cargo_home = match get_cargo_home(envmap) {
+        Some(cargo_home) => cargo_home,
+        None => return false,
+    };
+    ["credentials.toml", "credentials"].iter().any(|name| {
+        fs::read_to_string(cargo_home.join(name))
+            .ok()
+            .and_then(|buf| buf.parse::<toml::Table>().ok())
+            .and_then(|creds| {
+                creds
+                    .get("registries")?
+                    .as_table()
+                    .map(|regs| regs.values().any(|reg| reg.get("token").is_some()))
+            })
+            .unwrap_or(false)
+    })
+}
 
-    /// Detecting various information provided through the environment, especially Cargo.
-    ///
-    /// `built` writes something like
-    ///
-    /// ```rust,no_run
-    /// #[doc="The full version."]
-    /// pub const PKG_VERSION: &str = "1.2.3-rc1";
-    /// #[doc="The major version."]
-    /// pub const PKG_VERSION_MAJOR: &str = "1";
-    /// #[doc="The minor version."]
-    /// pub const PKG_VERSION_MINOR: &str = "2";
-    /// #[doc="The patch version."]
-    /// pub const PKG_VERSION_PATCH: &str = "3";
-    /// #[doc="The pre-release version."]
-    /// pub const PKG_VERSION_PRE: &str = "rc1";
-    /// #[doc="A colon-separated list of authors."]
-    /// pub const PKG_AUTHORS: &str = "Joe:Bob:Harry:Potter";
-    /// #[doc="The name of the package."]
-    /// pub const PKG_NAME: &str = "testbox";
-    /// #[doc="The description."]
-    /// pub const PKG_DESCRIPTION: &str = "xobtset";
-    /// #[doc="The home page."]
-    /// pub const PKG_HOMEPAGE: &str = "localhost";
-    /// #[doc="The target triple that was being compiled for."]
-    /// pub const TARGET: &str = "x86_64-apple-darwin";
-    /// #[doc="The host triple of the rust compiler."]
-    /// pub const HOST: &str = "x86_64-apple-darwin";
-    /// #[doc="`release` for release builds, `debug` for other builds."]
-    /// pub const PROFILE: &str = "debug";
-    /// #[doc="The compiler that cargo resolved to use."]
-    /// pub const RUSTC: &str = "rustc";
-    /// #[doc="The documentation generator that cargo resolved to use."]
-    /// pub const RUSTDOC: &str = "rustdoc";
-    /// #[doc="Value of OPT_LEVEL for the profile used during compilation."]
-    /// pub const OPT_LEVEL: &str = "0";
-    /// #[doc="The parallelism that was specified during compilation."]
-    /// pub const NUM_JOBS: u32 = 8;
-    /// #[doc="Value of DEBUG for the profile used during compilation."]
-    /// pub const DEBUG: bool = true;
-    /// ```
-    ///
-    pub fn set_env(&mut self, enabled: bool) -> &mut Self {
-        self.env = enabled;
-        self
-    }
+fn write_registry_auth(envmap: &EnvironmentMap, w: &mut CodeWriter) -> Result<(), Error> {
+    write_variable!(
+        w,
+        "PRIVATE_REGISTRY_AUTH_PRESENT",
+        "bool",
+        detect_registry_auth(envmap),
+        "If authentication for a private registry was configured during compilation."
+    );
+    Ok(())
+}
 
-    /// Parsing `Cargo.lock`and writing lists of dependencies and their versions.
-    ///
-    /// For this to work, `Cargo.lock` needs to actually be there; this is (usually)
-    /// only true for executables and not for libraries. Cargo will only create a
-    /// `Cargo.lock` for the top-level crate in a dependency-tree. In case
-    /// of a library, the top-level crate will decide which crate/version
-    /// combination to compile and there will be no `Cargo.lock` while the library
-    /// gets compiled as a dependency.
-    ///
-    /// Parsing `Cargo.lock` instead of `Cargo.toml` allows us to serialize the
-    /// precise versions Cargo chose to compile. One can't, however, distinguish
-    /// `build-dependencies`, `dev-dependencies` and `dependencies`. Furthermore,
-    /// some dependencies never show up if Cargo had not been forced to
-    /// actually use them (e.g. `dev-dependencies` with `cargo test` never
-    /// having been executed).
-    ///
-    /// ```rust,no_run
-    /// /// An array of effective dependencies as documented by `Cargo.lock`
-    /// pub const DEPENDENCIES: [(&str, &str); 2] = [("built", "0.1.0"), ("time", "0.1.36")];
-    /// /// The effective dependencies as a comma-separated string.
-    /// pub const DEPENDENCIES_STR: &str = "built 0.1.0, time 0.1.36";
-    /// ```
-    pub fn set_dependencies(&mut self, enabled: bool) -> &mut Self {
-        self.deps = enabled;
-        self
-    }
+/// The `RUST_*` environment variables that influence the build, if set.
+const BUILD_RUST_ENV_VARS: [&str; 2] = ["RUST_MIN_STACK", "RUST_BACKTRACE"];
 
-    /// Writing features enabled during build.
-    ///
-    /// One should not rely on this besides convenient debug output. If the runtime
-    /// depends on enabled features, use `#[cfg(feature = "foo")]` instead.
-    ///
-    /// ```rust,no_run
-    /// /// The features that were enabled during compilation.
-    /// pub const FEATURES: [&str; 2] = ["DEFAULT", "WAYLAND"];
-    /// /// The features as a comma-separated string.
-    /// pub const FEATURES_STR: &str = "DEFAULT, WAYLAND";
-    /// ```
-    pub fn set_features(&mut self, enabled: bool) -> &mut Self {
-        self.features = enabled;
-        self
-    }
+fn write_rust_env(envmap: &EnvironmentMap, w: &mut CodeWriter) -> Result<(), Error> {
+    let vars = BUILD_RUST_ENV_VARS
+        .iter()
+        .filter_map(|&k| envmap.get(k).map(|v| (k, v.as_str())))
+        .collect::<Vec<_>>();
+    write_variable!(
+        w,
+        "BUILD_RUST_ENV",
+        format!("[(&str, &str); {}]", vars.len()),
+        format!("{:?}", vars),
+        "The build-relevant `RUST_*` environment variables that were set during compilation."
+    );
+    Ok(())
+}
 
-    /// Writing the current timestamp.
-    ///
-    /// This option is only available if `built` is compiled with the
-    /// `chrono` feature.
-    ///
-    /// If `built` is included as a runtime-dependency, it can parse the
-    /// string-representation into a `time:Tm` with the help
-    /// of `built::util::strptime()`.
-    ///
-    /// ```rust,no_run
-    /// /// The built-time in RFC822, UTC
-    /// pub const BUILT_TIME_UTC: &str = "Tue, 14 Feb 2017 01:12:35 GMT";
-    /// ```
-    #[cfg(feature = "chrono")]
-    pub fn set_time(&mut self, enabled: bool) -> &mut Self {
-        self.time = enabled;
-        self
+/// The kernel's name and release as given by `uname -sr`, if available.
+fn get_build_kernel() -> Option<String> {
+    if !cfg!(unix) {
+        return None;
     }
-
-    /// Writing the configuration attributes.
-    ///
-    /// `built` writes something like
-    ///
-    /// ```rust,no_run
-    /// /// The target architecture, given by `CARGO_CFG_TARGET_ARCH`.
-    /// pub const CFG_TARGET_ARCH: &str = "x86_64";
-    /// /// The endianness, given by `CARGO_CFG_TARGET_ENDIAN`.
-    /// pub const CFG_ENDIAN: &str = "little";
-    /// /// The toolchain-environment, given by `CARGO_CFG_TARGET_ENV`.
-    /// pub const CFG_ENV: &str = "gnu";
-    /// /// The OS-family, given by `CARGO_CFG_TARGET_FAMILY`.
-    /// pub const CFG_FAMILY: &str = "unix";
-    /// /// The operating system, given by `CARGO_CFG_TARGET_OS`.
-    /// pub const CFG_OS: &str = "linux";
-    /// /// The pointer width, given by `CARGO_CFG_TARGET_POINTER_WIDTH`.
-    /// pub const CFG_POINTER_WIDTH: &str = "64";
-    /// ```
-    pub fn set_cfg(&mut self, enabled: bool) -> &mut Self {
-        self.cfg = enabled;
-        self
+    let output = process::Command::new("uname").arg("-sr").output().ok()?;
+    if !output.status.success() {
+        return None;
     }
+    let kernel = String::from_utf8(output.stdout).ok()?;
+    Some(kernel.trim().to_owned()).filter(|kernel| !kernel.is_empty())
+}
+
+fn write_build_host(w: &mut CodeWriter) -> Result<(), Error> {
+    write_str_variable!(
+        w,
+        "BUILD_OS",
+        env::consts::OS,
+        "The operating system of the machine that ran the build, as given by `std::env::consts::OS`."
+    );
+    write_str_variable!(
+        w,
+        "BUILD_ARCH",
+        env::consts::ARCH,
+        "The architecture of the machine that ran the build, as given by `std::env::consts::ARCH`."
+    );
+    write_variable!(
+        w,
+        "BUILD_KERNEL",
+        "Option<&str>",
+        fmt_option_str(get_build_kernel()),
+        "The kernel of the machine that ran the build, as given by `uname -sr`; `None` if unavailable."
+    );
+    Ok(())
 }
 
-/// Writes rust-code describing the crate at `manifest_location` to a new file named `dst`.
+/// Whether the crate in `manifest_location` is compiled as a path-dependency.
 ///
-/// # Errors
-/// The function returns an error if the file at `dst` already exists or can't
-/// be written to. This should not be a concern if the filename points to
-/// `OUR_DIR`.
-pub fn write_built_file_with_opts(
-    options: &Options,
+/// A crate that was not fetched by cargo and has no `Cargo.lock` in its own
+/// directory or in one of its parents (like the root of a workspace it is a
+/// member of) is considered a path-dependency.
+#[cfg(feature = "git2")]
+fn is_path_dependency(envmap: &EnvironmentMap, manifest_location: &path::Path) -> bool {
+    let fetched_by_cargo = get_cargo_home(envmap).is_some_and(|cargo_home| {
+        manifest_location.starts_with(cargo_home.join("registry"))
+            || manifest_location.starts_with(cargo_home.join("git"))
+    });
+    !fetched_by_cargo && find_lockfile(manifest_location).is_none()
+}
+
+#[cfg(feature = "git2")]
+fn write_likely_release_build(
+    envmap: &EnvironmentMap,
     manifest_location: &path::Path,
-    dst: &path::Path,
-) -> io::Result<()> {
-    let mut built_file = fs::File::create(&dst)?;
-    built_file.write_all(
-        r#"//
-// EVERYTHING BELOW THIS POINT WAS AUTO-GENERATED DURING COMPILATION. DO NOT MODIFY.
-//
-"#
-        .as_ref(),
+    conditions: &ReleaseConditions,
+    w: &mut CodeWriter,
+) -> Result<(), Error> {
+    let repo = util::discover_repo(manifest_location).ok().flatten();
+    let signals = ReleaseConditions {
+        git_clean: matches!(
+            repo.as_ref().map(util::repo_description),
+            Some(Ok((_, false)))
+        ),
+        git_tagged: matches!(repo.as_ref().map(util::repo_tagged), Some(Ok(true))),
+        not_path_dependency: !is_path_dependency(envmap, manifest_location),
+        ci: CIPlatform::detect_from_envmap(envmap).is_some(),
+    };
+    write_variable!(
+        w,
+        "LIKELY_RELEASE_BUILD",
+        "bool",
+        conditions.is_met_by(&signals),
+        "If the build looked like a release-build: The git-repository was clean, HEAD was \
+        tagged, the crate was not a path-dependency and a CI-platform was detected."
+    );
+    Ok(())
+}
+
+/// Writes the information taken from the `manifest` in `manifest_location`;
+/// everything is `None` without a manifest.
+fn write_manifest_info(
+    manifest_location: &path::Path,
+    manifest: Option<&toml::Table>,
+    w: &mut CodeWriter,
+) -> Result<(), Error> {
+    write_variable!(
+        w,
+        "LIB_NAME",
+        "Option<&str>",
+        fmt_option_str(manifest.and_then(parse_lib_name)),
+        "The name of the library-target, if explicitly set in `Cargo.toml`."
+    );
+    let edition = match manifest.map(parse_edition) {
+        None => None,
+        Some(Some(edition)) => Some(edition.to_owned()),
+        // Inherited via `edition.workspace = true`
+        Some(None) => manifest_location
+            .ancestors()
+            .skip(1)
+            .filter_map(|dir| get_manifest(dir).ok())
+            .find_map(|ws| {
+                ws.get("workspace")?
+                    .get("package")?
+                    .get("edition")?
+                    .as_str()
+                    .map(str::to_owned)
+            }),
+    };
+    write_variable!(
+        w,
+        "PKG_EDITION",
+        "Option<&str>",
+        fmt_option_str(edition.as_ref()),
+        "The edition of the package, as declared in `Cargo.toml`."
+    );
+    write_variable!(
+        w,
+        "MIN_EDITION_RUSTC",
+        "Option<&str>",
+        fmt_option_str(edition.as_deref().and_then(min_edition_rustc)),
+        "The minimum version of rustc supporting the edition of the package."
+    );
+    Ok(())
+}
+
+/// The edition declared in `manifest`; `None` if it is inherited from the workspace.
+///
+/// Manifests without an edition are of the 2015 edition.
+fn parse_edition(manifest: &toml::Table) -> Option<&str> {
+    match manifest.get("package").and_then(|p| p.get("edition")) {
+        Some(edition) => edition.as_str(),
+        None => Some("2015"),
+    }
+}
+
+/// The first version of rustc to support `edition`; `None` if the edition is unknown.
+fn min_edition_rustc(edition: &str) -> Option<&'static str> {
+    match edition {
+        "2015" => Some("1.0"),
+        "2018" => Some("1.31"),
+        "2021" => Some("1.56"),
+        "2024" => Some("1.85"),
+        _ => None,
+    }
+}
+
+fn write_dependencies(
+    envmap: &EnvironmentMap,
+    manifest_location: &path::Path,
+    yanked: bool,
+    w: &mut CodeWriter,
+) -> Result<(), Error> {
+    let lockfile = match get_lockfile(manifest_location) {
+        Ok(lockfile) => Some(lockfile),
+        Err(Error::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => {
+            println!(
+                "cargo:warning=No Cargo.lock found in {}, no dependencies are written",
+                manifest_location.display()
+            );
+            None
+        }
+        Err(e) => return Err(e),
+    };
+    write_variable!(
+        w,
+        "CARGO_LOCK_VERSION",
+        "u32",
+        lockfile.as_ref().map_or(0, parse_lockfile_version),
+        "The format-version of `Cargo.lock`; zero if there is none."
+    );
+    let deps = lockfile
+        .as_ref()
+        .map(parse_dependencies)
+        .unwrap_or_default();
+    write_variable!(
+        w,
+        "DEPENDENCIES",
+        format!("[(&str, &str); {}]", deps.len()),
+        format!("{:?}", deps),
+        "An array of effective dependencies as documented by `Cargo.lock`."
+    );
+    write_str_variable!(
+        w,
+        "DEPENDENCIES_STR",
+        deps.iter()
+            .map(|(n, v)| format!("{} {}", n, v))
+            .collect::<Vec<_>>()
+            .join(", "),
+        "The effective dependencies as a comma-separated string."
+    );
+    write_variable!(
+        w,
+        "DEPENDENCIES_COUNT",
+        "usize",
+        deps.len(),
+        "The number of `DEPENDENCIES`."
+    );
+    let sources = lockfile
+        .as_ref()
+        .map(parse_dependency_sources)
+        .unwrap_or_default();
+    write_variable!(
+        w,
+        "DEPENDENCIES_SOURCES",
+        format!("[&str; {}]", sources.len()),
+        format!("{:?}", sources),
+        "The kind of source of each of the `DEPENDENCIES`: `crates.io`, `registry`, `git` or `path`."
+    );
+    let resolution = match (&lockfile, get_manifest(manifest_location)) {
+        (Some(lockfile), Ok(manifest)) => parse_dependency_resolution(&manifest, lockfile),
+        _ => Vec::new(),
+    };
+    write_variable!(
+        w,
+        "DEPENDENCY_RESOLUTION",
+        format!("[(&str, &str, &str); {}]", resolution.len()),
+        format!("{:?}", resolution),
+        "The direct dependencies as `(name, requirement, resolved version)`, cross-referencing `Cargo.toml` and `Cargo.lock`."
+    );
+    let registries = lockfile
+        .as_ref()
+        .map(parse_dependencies_by_registry)
+        .unwrap_or_default();
+    write_variable!(
+        w,
+        "DEPENDENCIES_BY_REGISTRY",
+        format!("[(&str, &[&str]); {}]", registries.len()),
+        format!(
+            "[{}]",
+            registries
+                .iter()
+                .map(|(url, names)| format!("({:?}, &{:?})", url, names))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        "The names of the effective dependencies, grouped by the url of the registry they were retrieved from."
+    );
+    if yanked {
+        let yanked = lockfile
+            .as_ref()
+            .map(|lockfile| get_yanked_dependencies(envmap, lockfile))
+            .unwrap_or_default();
+        write_variable!(
+            w,
+            "YANKED_DEPENDENCIES",
+            format!("[&str; {}]", yanked.len()),
+            format!("{:?}", yanked),
+            "The dependencies (as `name version`) known to be yanked, according to the local copy of the registry-index."
+        );
+    }
+    Ok(())
+}
+
+/// The path of a crate's file in a registry-index, relative to the index' root.
+fn get_index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    }
+}
+
+/// Whether the index-entries in `index` mark `version` as yanked.
+///
+/// Both the newline-separated files of a git-index and the NUL-separated
+/// files of Cargo's index-cache are understood.
+fn is_yanked_in_index(index: &str, version: &str) -> bool {
+    index
+        .split(['\0', '\n'])
+        // Only the few yanked entries of the version in question get parsed
+        .filter(|entry| {
+            entry.starts_with('{') && entry.contains("\"yanked\":true") && entry.contains(version)
+        })
+        .filter_map(|entry| util::parse_built_json(entry).ok())
+        .any(|entry| {
+            matches!(entry.get("vers"), Some(util::BuildInfoValue::String(v)) if v == version)
+                && entry.get("yanked") == Some(&util::BuildInfoValue::Bool(true))
+        })
+}
+
+/// The hosts of the registry-index `source`, as used for the index-directories
+/// below `CARGO_HOME`; crates.io has a git- and a sparse-index.
+fn get_index_hosts(source: &cargo_lock::SourceId) -> Vec<&str> {
+    if source.is_default_registry() {
+        vec!["index.crates.io", "github.com"]
+    } else {
+        source.url().host_str().into_iter().collect()
+    }
+}
+
+/// The dependencies retrieved from a registry that are marked as yanked in the
+/// local copies of the registry-indices below `CARGO_HOME`.
+///
+/// This is best-effort: Nothing is fetched and yanks that happened after the
+/// local copies were last updated are not known.
+fn get_yanked_dependencies(
+    envmap: &EnvironmentMap,
+    lockfile: &cargo_lock::Lockfile,
+) -> Vec<String> {
+    let index_dirs = get_cargo_home(envmap)
+        .and_then(|cargo_home| fs::read_dir(cargo_home.join("registry").join("index")).ok())
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let mut yanked = Vec::new();
+    for package in &lockfile.packages {
+        let source = match &package.source {
+            Some(source) if source.is_registry() => source,
+            _ => continue,
+        };
+        let hosts = get_index_hosts(source);
+        let index_path = get_index_path(package.name.as_str());
+        let version = package.version.to_string();
+        let is_yanked = index_dirs
+            .iter()
+            .filter(|dir| {
+                // Cargo names the directories `<host>-<hash of the source>`
+                dir.file_name()
+                    .and_then(ffi::OsStr::to_str)
+                    .and_then(|name| name.rsplit_once('-'))
+                    .is_some_and(|(host, _)| hosts.contains(&host))
+            })
+            .any(|dir| {
+                [dir.join(".cache").join(&index_path), dir.join(&index_path)]
+                    .iter()
+                    .filter_map(|path| fs::read(path).ok())
+                    .any(|index| is_yanked_in_index(&String::from_utf8_lossy(&index), &version))
+            });
+        if is_yanked {
+            yanked.push(format!("{} {}", package.name, version));
+        }
+    }
+    yanked.sort_unstable();
+    yanked
+}
+
+/// A 64-bit FNV-1a hash.
+///
+/// The result is stable across platforms and compiler versions, which the
+/// hashers from `std` do not guarantee. It is not a cryptographic hash.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl hash::Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= u64::from(*b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The paths of all files below `dir`, relative to `root` and `/`-separated.
+fn walk_dir(root: &path::Path, dir: &path::Path, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk_dir(root, &path, files)?;
+        } else if let Ok(rel) = path.strip_prefix(root) {
+            let components = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>();
+            files.push(components.join("/"));
+        }
+    }
+    Ok(())
+}
+
+/// The paths of the files tracked by git below `manifest_location`, relative
+/// to the manifest and `/`-separated; `None` if there is no git-repository.
+#[cfg(feature = "git2")]
+fn get_tracked_files(manifest_location: &path::Path) -> Option<Vec<String>> {
+    let mut prefix = util::get_repo_relative_path(manifest_location).ok()??;
+    if !prefix.is_empty() {
+        prefix.push('/');
+    }
+    let index = git2::Repository::discover(manifest_location)
+        .ok()?
+        .index()
+        .ok()?;
+    let files = index
+        .iter()
+        .filter_map(|entry| {
+            String::from_utf8_lossy(&entry.path)
+                .strip_prefix(&prefix)
+                .map(ToOwned::to_owned)
+        })
+        .collect();
+    Some(files)
+}
+
+/// Hash the contents of the crate's source files.
+///
+/// The files are those tracked by git below `manifest_location`, or all
+/// files below `src/` if there is no git-repository.
+fn get_source_tree_hash(manifest_location: &path::Path) -> io::Result<String> {
+    use hash::Hasher;
+
+    #[cfg(feature = "git2")]
+    let tracked = get_tracked_files(manifest_location);
+    #[cfg(not(feature = "git2"))]
+    let tracked = None;
+    let mut files = match tracked {
+        Some(files) => files,
+        None => {
+            let mut files = Vec::new();
+            walk_dir(
+                manifest_location,
+                &manifest_location.join("src"),
+                &mut files,
+            )?;
+            files
+        }
+    };
+    files.sort_unstable();
+
+    let mut hasher = Fnv1a::default();
+    for file in &files {
+        let content = match fs::read(manifest_location.join(file)) {
+            Ok(content) => content,
+            // Tracked by git, but deleted from the working directory
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.write(file.as_bytes());
+        hasher.write_u8(0);
+        hasher.write_u64(content.len() as u64);
+        hasher.write(&content);
+    }
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+fn write_source_hash(manifest_location: &path::Path, w: &mut CodeWriter) -> Result<(), Error> {
+    write_str_variable!(
+        w,
+        "SOURCE_TREE_HASH",
+        get_source_tree_hash(manifest_location)?,
+        "A hash of the contents of the crate's source files."
+    );
+    Ok(())
+}
+
+#[cfg(feature = "chrono")]
+fn write_time(
+    now: &chrono::DateTime<chrono::offset::Utc>,
+    time_zone: Option<chrono::FixedOffset>,
+    w: &mut CodeWriter,
+) -> Result<(), Error> {
+    write_str_variable!(
+        w,
+        "BUILT_TIME_UTC",
+        now.to_rfc2822(),
+        "The build time in RFC2822, UTC."
+    );
+    write_variable!(
+        w,
+        "BUILT_TIME_UNIX",
+        "i64",
+        now.timestamp(),
+        "The build time in seconds since the Unix epoch."
+    );
+    if let Some(time_zone) = time_zone {
+        write_str_variable!(
+            w,
+            "BUILT_TIME_TZ",
+            now.with_timezone(&time_zone).to_rfc2822(),
+            "The build time in RFC2822, at a fixed offset from UTC."
+        );
+    }
+    Ok(())
+}
+
+/// The time given by `SOURCE_DATE_EPOCH` for reproducible builds, the current time otherwise.
+#[cfg(feature = "chrono")]
+fn get_build_time(envmap: &EnvironmentMap) -> chrono::DateTime<chrono::offset::Utc> {
+    use chrono::TimeZone;
+
+    envmap
+        .get("SOURCE_DATE_EPOCH")
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .and_then(|epoch| chrono::offset::Utc.timestamp_opt(epoch, 0).single())
+        .unwrap_or_else(chrono::offset::Utc::now)
+}
+
+#[cfg(feature = "chrono")]
+fn write_stale_check(
+    now: &chrono::DateTime<chrono::offset::Utc>,
+    w: &mut CodeWriter,
+) -> Result<(), Error> {
+    writeln!(
+        w,
+        r#"{}fn {}(max_days: i64) -> bool {{
+    const BUILT_AT: i64 = {};
+    let now = match ::std::time::SystemTime::now().duration_since(::std::time::UNIX_EPOCH) {{
+        Ok(d) => d.as_secs() as i64,
+        Err(_) => return false,
+    }};
+    let age_days = (now - BUILT_AT) / 86400;
+    if age_days > max_days {{
+        eprintln!(
+            "Warning: This binary was built on {{}}, {{}} days ago.",
+            {}, age_days
+        );
+        true
+    }} else {{
+        false
+    }}
+}}"#,
+        w.header(
+            "Prints a warning to stderr if this binary was built more than `max_days` days ago.\n\n\
+             Returns `true` if the build is considered stale."
+        ),
+        w.fn_name("warn_if_stale"),
+        now.timestamp(),
+        w.name("BUILT_TIME_UTC"),
+    )?;
+    Ok(())
+}
+
+/// Writes the `CFG_*` constants, given the `CARGO_CFG_*` variables in `cfg`.
+fn write_cfg(cfg: &EnvironmentMap, w: &mut CodeWriter) -> Result<(), Error> {
+    let get_env = |name: &str| cfg.get(name).map_or("", String::as_str);
+
+    let target_arch = get_env("CARGO_CFG_TARGET_ARCH");
+    let target_endian = get_env("CARGO_CFG_TARGET_ENDIAN");
+    let target_env = get_env("CARGO_CFG_TARGET_ENV");
+    let target_family = get_env("CARGO_CFG_TARGET_FAMILY");
+    let target_os = get_env("CARGO_CFG_TARGET_OS");
+    let target_pointer_width = get_env("CARGO_CFG_TARGET_POINTER_WIDTH");
+    let target_vendor = get_env("CARGO_CFG_TARGET_VENDOR");
+
+    write_str_variable!(
+        w,
+        "CFG_TARGET_ARCH",
+        target_arch,
+        "The target architecture, given by `CARGO_CFG_TARGET_ARCH`."
+    );
+
+    write_str_variable!(
+        w,
+        "CFG_ENDIAN",
+        target_endian,
+        "The endianness, given by `CARGO_CFG_TARGET_ENDIAN`."
+    );
+
+    write_str_variable!(
+        w,
+        "CFG_ENV",
+        target_env,
+        "The toolchain-environment, given by `CARGO_CFG_TARGET_ENV`."
+    );
+
+    write_str_variable!(
+        w,
+        "CFG_FAMILY",
+        target_family,
+        "The OS-family, given by `CARGO_CFG_TARGET_FAMILY`."
+    );
+
+    write_str_variable!(
+        w,
+        "CFG_OS",
+        target_os,
+        "The operating system, given by `CARGO_CFG_TARGET_OS`."
+    );
+
+    write_variable!(
+        w,
+        "CFG_POINTER_WIDTH",
+        "u8",
+        target_pointer_width.parse::<u8>().unwrap_or(0),
+        "The pointer width in bits, given by `CARGO_CFG_TARGET_POINTER_WIDTH`; zero if unknown."
+    );
+
+    write_str_variable!(
+        w,
+        "CFG_TARGET_VENDOR",
+        target_vendor,
+        "The target vendor, given by `CARGO_CFG_TARGET_VENDOR`."
+    );
+
+    Ok(())
+}
+
+fn write_target_endianness(cfg: &EnvironmentMap, w: &mut CodeWriter) -> Result<(), Error> {
+    write_endianness(
+        cfg.get("CARGO_CFG_TARGET_ENDIAN")
+            .map_or("", String::as_str),
+        w,
+    )
+}
+
+fn write_endianness(target_endian: &str, w: &mut CodeWriter) -> Result<(), Error> {
+    let variant = match target_endian {
+        "little" => "Little",
+        "big" => "Big",
+        _ => return Ok(()),
+    };
+    let enum_name = w.type_name("Endianness");
+    writeln!(
+        w,
+        "#[derive(Clone, Copy, Debug, PartialEq, Eq)]\n{}enum {} {{",
+        w.header("The endianness of the target."),
+        enum_name
+    )?;
+    for (name, doc) in &[("Little", "Little endian."), ("Big", "Big endian.")] {
+        if w.public {
+            writeln!(w, "    #[doc=r#\"{}\"#]", doc)?;
+        }
+        writeln!(w, "    {},", name)?;
+    }
+    writeln!(w, "}}")?;
+    write_variable!(
+        w,
+        "ENDIANNESS",
+        enum_name,
+        format!("{}::{}", enum_name, variant),
+        "The endianness as an enum, given by `CARGO_CFG_TARGET_ENDIAN`."
+    );
+    Ok(())
+}
+
+fn write_generated_assets(assets: &[(String, String)], w: &mut CodeWriter) -> Result<(), Error> {
+    write_variable!(
+        w,
+        "GENERATED_ASSETS",
+        format!("[(&str, &str); {}]", assets.len()),
+        format!("{:?}", assets),
+        "Assets generated by the build-script, by name and path relative to `OUT_DIR`."
+    );
+    let mut arms = String::new();
+    for (name, path) in assets {
+        arms.push_str(&format!(
+            "        {} => Some(concat!(env!(\"OUT_DIR\"), \"/\", {})),\n",
+            escape_str(name),
+            escape_str(path)
+        ));
+    }
+    writeln!(
+        w,
+        "{}fn {}(name: &str) -> Option<&'static str> {{\n    match name {{\n{}        _ => None,\n    }}\n}}",
+        w.header("The full path of the generated asset `name`, if it was registered."),
+        w.fn_name("generated_asset"),
+        arms
     )?;
+    Ok(())
+}
+
+/// The files the information written due to `options` depends on; none if
+/// `set_rerun_if_changed()` is disabled.
+///
+/// These are `Cargo.lock` if dependencies are written and the files of the
+/// git-repository which change with HEAD and the index. If there are any,
+/// `Cargo.toml` and the package's own files (see `get_package_entries()`)
+/// are included as well; once anything is registered, Cargo no longer reruns
+/// the build-script if any of the package's files changes. Paths below
+/// `manifest_location` are relative to it.
+fn get_rebuild_triggers(options: &Options, manifest_location: &path::Path) -> Vec<String> {
+    if !options.rerun_if_changed {
+        return Vec::new();
+    }
+    let mut triggers = Vec::new();
+    if options.deps {
+        triggers.extend(find_lockfile(manifest_location));
+    }
+    #[cfg(feature = "git2")]
+    {
+        if options.git {
+            if let Ok(Some(repo)) = util::discover_repo(manifest_location) {
+                let git_dir = repo.path();
+                triggers.push(git_dir.join("HEAD"));
+                if let Ok(head) = repo.find_reference("HEAD") {
+                    triggers.extend(
+                        head.symbolic_target()
+                            .map(|target| git_dir.join(target))
+                            .filter(|path| path.exists()),
+                    );
+                }
+                triggers.extend(
+                    ["packed-refs", "index"]
+                        .iter()
+                        .map(|name| git_dir.join(name))
+                        .filter(|path| path.exists()),
+                );
+            }
+        }
+    }
+    if !triggers.is_empty() {
+        triggers.insert(0, manifest_location.join("Cargo.toml"));
+        triggers.extend(get_package_entries(manifest_location));
+    }
+    let mut seen = collections::HashSet::new();
+    triggers.retain(|path| seen.insert(path.clone()));
+    triggers
+        .iter()
+        .map(|path| {
+            path.strip_prefix(manifest_location)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+/// The top-level files and directories of the package in `manifest_location`.
+///
+/// Cargo scans registered directories recursively, so hidden entries like
+/// `.git` and the target-directory are skipped; the latter changes with
+/// every build and would cause the build-script to rerun every time.
+fn get_package_entries(manifest_location: &path::Path) -> Vec<path::PathBuf> {
+    let out_dir = env::var_os("OUT_DIR").map(path::PathBuf::from);
+    let mut entries = match fs::read_dir(manifest_location) {
+        Ok(dir) => dir
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                !name.starts_with('.')
+                    && name != "target"
+                    && !out_dir
+                        .as_ref()
+                        .is_some_and(|out_dir| out_dir.starts_with(path))
+            })
+            .collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+    entries.sort();
+    entries
+}
+
+/// The environment variables the information depends on, to be registered
+/// via `cargo:rerun-if-env-changed`.
+fn get_rerun_env_vars(options: &Options) -> Vec<&'static str> {
+    let mut vars = Vec::new();
+    if options.ci || options.likely_release_build {
+        vars.extend(CI_ENV_VARS.iter());
+    }
+    if cfg!(feature = "chrono") && options.time {
+        vars.push("SOURCE_DATE_EPOCH");
+    }
+    if options.rust_env {
+        vars.extend(BUILD_RUST_ENV_VARS.iter());
+    }
+    vars
+}
+
+fn write_rebuild_triggers(triggers: &[String], w: &mut CodeWriter) -> Result<(), Error> {
+    write_variable!(
+        w,
+        "REBUILD_TRIGGERS",
+        format!("[&str; {}]", triggers.len()),
+        format!("{:?}", triggers),
+        "The files registered via `cargo:rerun-if-changed`, the information above depends on."
+    );
+    Ok(())
+}
+
+fn write_gather_duration(duration: time::Duration, w: &mut CodeWriter) -> Result<(), Error> {
+    write_variable!(
+        w,
+        "BUILT_GATHER_DURATION_MS",
+        "u64",
+        std::convert::TryFrom::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+        "The time it took `built` to gather the information above, in milliseconds."
+    );
+    Ok(())
+}
+
+fn write_json(w: &mut CodeWriter) -> Result<(), Error> {
+    let json = items_to_json(&w.items);
+    write_str_variable!(
+        w,
+        "BUILT_JSON",
+        json,
+        "All of the information above, as a JSON-object."
+    );
+    Ok(())
+}
+
+/// Writes a struct holding all constants emitted so far and a `const fn` returning it
+/// and/or a constant of it.
+fn write_info_struct(const_fn: bool, info_const: bool, w: &mut CodeWriter) -> Result<(), Error> {
+    let doc = "All of the information above, in a single value.";
+    let struct_name = w.type_name("BuiltInfo");
+    writeln!(
+        w,
+        "#[derive(Clone, Copy, Debug)]\n{}struct {} {{",
+        w.header(doc),
+        struct_name
+    )?;
+    let mut fields = String::new();
+    for item in &w.items {
+        if w.public {
+            fields.push_str(&format!("    #[doc=r#\"{}\"#]\n", item.doc));
+        }
+        fields.push_str(&item.cfg_attr("    "));
+        fields.push_str(&format!(
+            "    pub {}: {},\n",
+            item.name.to_lowercase(),
+            item.field_type()
+        ));
+    }
+    writeln!(w, "{}}}", fields)?;
+    fn field_values(w: &CodeWriter, indent: &str) -> String {
+        let mut values = String::new();
+        for item in &w.items {
+            values.push_str(&item.cfg_attr(indent));
+            values.push_str(&format!(
+                "{}{}: {},\n",
+                indent,
+                item.name.to_lowercase(),
+                w.name(&item.name)
+            ));
+        }
+        values
+    }
+    if const_fn {
+        let values = field_values(w, "        ");
+        writeln!(
+            w,
+            "{}const fn {}() -> {} {{\n    {} {{\n{}    }}\n}}",
+            w.header(doc),
+            w.fn_name("built_info"),
+            struct_name,
+            struct_name,
+            values
+        )?;
+    }
+    if info_const {
+        let values = field_values(w, "    ");
+        writeln!(
+            w,
+            "{}const {}: {} = {} {{\n{}}};",
+            w.header(doc),
+            w.name("INFO"),
+            struct_name,
+            struct_name,
+            values
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes a function returning the package's version as a `semver::Version`.
+#[cfg(feature = "semver")]
+fn write_pkg_version_fn(envmap: &EnvironmentMap, w: &mut CodeWriter) -> Result<(), Error> {
+    let version = get_env_var(envmap, "CARGO_PKG_VERSION")?;
+    let parsed = semver::Version::parse(version)
+        .map_err(|_| Error::InvalidEnv("CARGO_PKG_VERSION", version.to_owned()))?;
+    // Both were validated above, but `semver` has no `const`-constructor for
+    // them; non-empty values are parsed again at runtime.
+    let pre = if parsed.pre.is_empty() {
+        "built::semver::Prerelease::EMPTY".to_owned()
+    } else {
+        format!(
+            "built::semver::Prerelease::new({:?}).expect(\"The pre-release was validated at build-time\")",
+            parsed.pre.as_str()
+        )
+    };
+    let build = if parsed.build.is_empty() {
+        "built::semver::BuildMetadata::EMPTY".to_owned()
+    } else {
+        format!(
+            "built::semver::BuildMetadata::new({:?}).expect(\"The build-metadata was validated at build-time\")",
+            parsed.build.as_str()
+        )
+    };
+    writeln!(
+        w,
+        "{}fn {}() -> built::semver::Version {{\n    built::semver::Version {{\n        major: {},\n        minor: {},\n        patch: {},\n        pre: {},\n        build: {},\n    }}\n}}",
+        w.header("The version of the package, as a `semver::Version`."),
+        w.fn_name("pkg_version"),
+        parsed.major,
+        parsed.minor,
+        parsed.patch,
+        pre,
+        build
+    )?;
+    Ok(())
+}
+
+/// Writes a trait with one method per constant emitted so far and a zero-sized type implementing it.
+fn write_ext_trait(w: &mut CodeWriter) -> Result<(), Error> {
+    let trait_name = w.type_name("BuiltInfoExt");
+    let struct_name = w.type_name("BuildInfo");
+    writeln!(
+        w,
+        "{}trait {} {{",
+        w.header("Access to all of the information above by method."),
+        trait_name
+    )?;
+    let mut decls = String::new();
+    let mut impls = String::new();
+    for item in &w.items {
+        let method = item.name.to_lowercase();
+        if w.public {
+            decls.push_str(&format!("    #[doc=r#\"{}\"#]\n", item.doc));
+        }
+        decls.push_str(&item.cfg_attr("    "));
+        impls.push_str(&item.cfg_attr("    "));
+        decls.push_str(&format!(
+            "    fn {}(&self) -> {};\n",
+            method,
+            item.field_type()
+        ));
+        impls.push_str(&format!(
+            "    fn {}(&self) -> {} {{\n        {}\n    }}\n",
+            method,
+            item.field_type(),
+            w.name(&item.name)
+        ));
+    }
+    writeln!(w, "{}}}", decls)?;
+    writeln!(
+        w,
+        "#[derive(Clone, Copy, Debug, Default)]\n{}struct {};\n",
+        w.header(&format!("A zero-sized type implementing `{}`.", trait_name)),
+        struct_name
+    )?;
+    writeln!(w, "impl {} for {} {{\n{}}}", trait_name, struct_name, impls)?;
+    Ok(())
+}
+
+/// Package information supplied explicitly instead of by Cargo.
+///
+/// Build systems other than Cargo (like Bazel or Buck) do not provide the
+/// `CARGO_PKG_*` environment variables. These facts can be given via
+/// [`Options::set_package_metadata`][opt] instead.
+///
+/// [opt]: struct.Options.html#method.set_package_metadata
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PackageMetadata {
+    /// The name of the package.
+    pub name: String,
+    /// The full version, like `1.2.3-rc1`.
+    pub version: String,
+    /// The authors of the package.
+    pub authors: Vec<String>,
+    /// The description.
+    pub description: String,
+    /// The homepage.
+    pub homepage: String,
+    /// The license.
+    pub license: String,
+    /// The source repository.
+    pub repository: String,
+}
+
+impl PackageMetadata {
+    /// Overrides the `CARGO_PKG_*` variables in `envmap`.
+    ///
+    /// The variables in `REQUIRED_ENV_VARS` are set to fallbacks if missing,
+    /// as only Cargo provides them.
+    fn apply(&self, envmap: &mut EnvironmentMap) {
+        let version = self.version.split('+').next().unwrap_or_default();
+        let (version_core, version_pre) = match version.find('-') {
+            Some(idx) => (&version[..idx], &version[idx + 1..]),
+            None => (version, ""),
+        };
+        let mut version_parts = version_core.splitn(3, '.');
+        let mut vars = vec![
+            ("CARGO_PKG_NAME", self.name.clone()),
+            ("CARGO_PKG_VERSION", self.version.clone()),
+            ("CARGO_PKG_VERSION_PRE", version_pre.to_owned()),
+            ("CARGO_PKG_AUTHORS", self.authors.join(":")),
+            ("CARGO_PKG_DESCRIPTION", self.description.clone()),
+            ("CARGO_PKG_HOMEPAGE", self.homepage.clone()),
+            ("CARGO_PKG_LICENSE", self.license.clone()),
+            ("CARGO_PKG_REPOSITORY", self.repository.clone()),
+        ];
+        for name in &[
+            "CARGO_PKG_VERSION_MAJOR",
+            "CARGO_PKG_VERSION_MINOR",
+            "CARGO_PKG_VERSION_PATCH",
+        ] {
+            vars.push((name, version_parts.next().unwrap_or_default().to_owned()));
+        }
+        for (k, v) in vars {
+            envmap.insert(k.to_owned(), v);
+        }
+        for &name in &REQUIRED_ENV_VARS {
+            let fallback = match name {
+                "RUSTC" => "rustc",
+                "RUSTDOC" => "rustdoc",
+                "NUM_JOBS" => "0",
+                "DEBUG" => "false",
+                _ => "",
+            };
+            envmap
+                .entry(name.to_owned())
+                .or_insert_with(|| fallback.to_owned());
+        }
+    }
+}
+
+/// The conditions combined into `LIKELY_RELEASE_BUILD`.
+///
+/// Every field that is `true` is a condition that has to hold for a build to
+/// be considered a release-build; setting a field to `false` ignores that
+/// condition. See [`Options::set_likely_release_build`][opt].
+///
+/// [opt]: struct.Options.html#method.set_likely_release_build
+#[cfg(feature = "git2")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReleaseConditions {
+    /// The git-repository has no dirty/staged files.
+    pub git_clean: bool,
+    /// HEAD is exactly on a tag.
+    pub git_tagged: bool,
+    /// The crate is not compiled as a path-dependency. This holds if the crate
+    /// was fetched by Cargo (from a registry or a git-source) or if there is a
+    /// `Cargo.lock` next to its manifest or in a parent directory, as is the
+    /// case for the top-level crate and the members of its workspace.
+    pub not_path_dependency: bool,
+    /// A Continuous Integration platform was detected.
+    pub ci: bool,
+}
+
+#[cfg(feature = "git2")]
+impl Default for ReleaseConditions {
+    /// All conditions are required.
+    fn default() -> Self {
+        ReleaseConditions {
+            git_clean: true,
+            git_tagged: true,
+            not_path_dependency: true,
+            ci: true,
+        }
+    }
+}
+
+#[cfg(feature = "git2")]
+impl ReleaseConditions {
+    /// Whether all the conditions required by `self` are met by `signals`.
+    fn is_met_by(&self, signals: &ReleaseConditions) -> bool {
+        (!self.git_clean || signals.git_clean)
+            && (!self.git_tagged || signals.git_tagged)
+            && (!self.not_path_dependency || signals.not_path_dependency)
+            && (!self.ci || signals.ci)
+    }
+}
+
+/// A callback receiving the label of each completed phase, see `Options::set_progress()`.
+type ProgressFn = dyn Fn(&str);
+
+/// Selects which information `built` should retrieve and write as Rust code.
+/// Used in conjunction with [`write_built_file_with_opts`][wrt].
+///
+/// [wrt]: fn.write_built_file_with_opts.html
+#[allow(unused)]
+pub struct Options {
+    compiler: bool,
+    git: bool,
+    hg: bool,
+    ci: bool,
+    env: bool,
+    deps: bool,
+    yanked_deps: bool,
+    features: bool,
+    time: bool,
+    #[cfg(feature = "chrono")]
+    time_zone: Option<chrono::FixedOffset>,
+    stale_check: bool,
+    cfg: bool,
+    source_hash: bool,
+    const_fn: bool,
+    info_struct: bool,
+    ext_trait: bool,
+    git_status_max_len: usize,
+    git_first_parent: bool,
+    release_branch_patterns: Vec<String>,
+    likely_release_build: bool,
+    #[cfg(feature = "git2")]
+    release_conditions: ReleaseConditions,
+    registry_auth: bool,
+    rust_env: bool,
+    build_host: bool,
+    endianness_enum: bool,
+    json: bool,
+    pkg_version_fn: bool,
+    gather_duration: bool,
+    rerun_if_changed: bool,
+    public: bool,
+    name_prefix: String,
+    package_metadata: Option<PackageMetadata>,
+    generated_assets: Vec<(String, String)>,
+    cfg_gate: Option<String>,
+    overwrite: bool,
+    postprocess: Option<Box<dyn Fn(String) -> String>>,
+    progress: Option<Box<ProgressFn>>,
+}
+
+impl Default for Options {
+    /// A new struct with almost all options enabled.
+    ///
+    /// Parsing and writing information about dependencies is disabled by default
+    /// because this information is not available for crates compiled as dependencies;
+    /// only the top-level crate gets to do this. See `set_dependencies()` for
+    /// further information.
+    ///
+    fn default() -> Options {
+        Options {
+            compiler: true,
+            git: true,
+            hg: true,
+            ci: true,
+            env: true,
+            deps: false,
+            yanked_deps: false,
+            features: true,
+            time: true,
+            #[cfg(feature = "chrono")]
+            time_zone: None,
+            stale_check: false,
+            cfg: true,
+            source_hash: false,
+            const_fn: false,
+            info_struct: false,
+            ext_trait: false,
+            git_status_max_len: 1024,
+            git_first_parent: false,
+            release_branch_patterns: Vec::new(),
+            likely_release_build: false,
+            #[cfg(feature = "git2")]
+            release_conditions: ReleaseConditions::default(),
+            registry_auth: false,
+            rust_env: false,
+            build_host: false,
+            endianness_enum: false,
+            json: false,
+            pkg_version_fn: false,
+            gather_duration: false,
+            rerun_if_changed: true,
+            public: true,
+            name_prefix: String::new(),
+            package_metadata: None,
+            generated_assets: Vec::new(),
+            cfg_gate: None,
+            overwrite: true,
+            postprocess: None,
+            progress: None,
+        }
+    }
+}
+
+impl Options {
+    /// All information available with the features `built` was compiled with.
+    ///
+    /// Everything is enabled, including dependencies, the stale-check and the
+    /// additional forms of output like `set_const_fn()` and `set_json()`. The
+    /// exception is `set_pkg_version_fn()`, as its code requires `built` with
+    /// the `semver`-feature as a regular dependency. Settings which don't
+    /// select information, like `set_rerun_if_changed()`, `set_public()` or
+    /// `set_name_prefix()`, keep their defaults.
+    ///
+    /// ```rust,no_run
+    /// let options = built::Options::all();
+    /// ```
+    #[must_use]
+    pub fn all() -> Options {
+        Options::with_all(true)
+    }
+
+    /// No information at all; the setters enable exactly what is wanted.
+    ///
+    /// ```rust,no_run
+    /// let mut options = built::Options::none();
+    /// options.set_env(true);
+    /// ```
+    #[must_use]
+    pub fn none() -> Options {
+        Options::with_all(false)
+    }
+
+    fn with_all(enabled: bool) -> Options {
+        let git = enabled && cfg!(feature = "git2");
+        let time = enabled && cfg!(feature = "chrono");
+        Options {
+            compiler: enabled,
+            git,
+            hg: enabled && cfg!(feature = "hg"),
+            ci: enabled,
+            env: enabled,
+            deps: enabled,
+            yanked_deps: enabled,
+            features: enabled,
+            time,
+            stale_check: time,
+            cfg: enabled,
+            source_hash: enabled,
+            const_fn: enabled,
+            info_struct: enabled,
+            ext_trait: enabled,
+            git_first_parent: git,
+            likely_release_build: git,
+            registry_auth: enabled,
+            rust_env: enabled,
+            build_host: enabled,
+            endianness_enum: enabled,
+            json: enabled,
+            gather_duration: enabled,
+            ..Options::default()
+        }
+    }
+
+    /// A preset for the given profile, as given by the `PROFILE` environment variable.
+    ///
+    /// For `debug`, a leaner configuration is returned, which does not call
+    /// the compiler and does not parse dependencies or acquire the build time;
+    /// this avoids needless work and rebuilds during development. For `release`,
+    /// dependencies are written in addition to the default configuration. Other
+    /// profiles get the default configuration.
+    ///
+    /// ```rust,no_run
+    /// let profile = std::env::var("PROFILE").unwrap();
+    /// let options = built::Options::for_profile(&profile);
+    /// ```
+    #[must_use]
+    pub fn for_profile(profile: &str) -> Options {
+        let mut options = Options::default();
+        match profile {
+            "debug" => {
+                options.compiler = false;
+                options.deps = false;
+                options.time = false;
+            }
+            "release" => {
+                options.compiler = true;
+                options.deps = true;
+            }
+            _ => {}
+        }
+        options
+    }
+
+    /// Enabling or disabling the main categories of information at once.
+    ///
+    /// This sets `set_compiler()`, `set_git()`, `set_ci()`, `set_env()`,
+    /// `set_dependencies()`, `set_features()` and `set_time()` together; `git`
+    /// and `time` are only enabled if `built` was compiled with the `git2`
+    /// and `chrono` features, respectively. Other options are left as they are.
+    ///
+    /// ```rust,no_run
+    /// let mut options = built::Options::default();
+    /// options.set_all(false).set_env(true);
+    /// ```
+    pub fn set_all(&mut self, enabled: bool) -> &mut Self {
+        self.compiler = enabled;
+        self.git = enabled && cfg!(feature = "git2");
+        self.ci = enabled;
+        self.env = enabled;
+        self.deps = enabled;
+        self.features = enabled;
+        self.time = enabled && cfg!(feature = "chrono");
+        self
+    }
+
+    /// Detecting and writing the version of `RUSTC` and `RUSTDOC`.
+    ///
+    /// Call the values of `RUSTC` and `RUSTDOC` as provided by Cargo to get a version string. The
+    /// result will something like
+    ///
+    /// ```rust,no_run
+    /// pub const RUSTC_VERSION: &str = "rustc 1.15.0";
+    /// pub const RUSTDOC_VERSION: &str = "rustdoc 1.15.0";
+    /// pub const RUSTC_CHANNEL: Option<&str> = Some("stable");
+    /// pub const RUSTC_COMMIT_HASH: Option<&str> = Some("e4f4d2e3e1ac3b4e7c69b8a8a0c8e6a9d54b1f5b");
+    /// pub const RUSTC_COMMIT_DATE: Option<&str> = Some("2017-02-01");
+    /// pub const RUSTC_HOST: Option<&str> = Some("x86_64-unknown-linux-gnu");
+    /// pub const RUSTC_LLVM_VERSION: Option<&str> = Some("3.9");
+    /// ```
+    pub fn set_compiler(&mut self, enabled: bool) -> &mut Self {
+        self.compiler = enabled;
+        self
+    }
+
+    /// Detecting and writing the tag or commit-id and a flag to indicate
+    /// a dirty working directory of the crate's git repository (if any).
+    ///
+    /// This option is only available if `built` was compiled with the
+    /// `git2` feature.
+    ///
+    /// Try to open the git-repository at `manifest_location` and retrieve `HEAD`
+    /// tag or commit id.  The result will be something like
+    ///
+    /// ```rust,no_run
+    /// pub const GIT_VERSION: Option<&str> = Some("0.1");
+    /// pub const GIT_COMMITS_SINCE_TAG: Option<u32> = Some(0);
+    /// pub const GIT_DIRTY: Option<bool> = Some(false);
+    /// pub const GIT_COMMIT_HASH: Option<&str> = Some("18b2eabfb47998c296f9d5183f617f1b1cc2d321");
+    /// pub const GIT_COMMIT_HASH_SHORT: Option<&str> = Some("18b2eab");
+    /// pub const GIT_COMMIT_PARENTS: [&str; 1] = ["0c2d0e1c5a0a6d2bf4ba9ab5b2ff6d3ac3b2c1a9"];
+    /// pub const GIT_HEAD_TAGS: [&str; 1] = ["0.1"];
+    /// pub const GIT_HEAD_REF: Option<&str> = Some("refs/heads/master");
+    /// pub const GIT_HEAD_DETACHED: Option<bool> = Some(false);
+    /// pub const GIT_DEFAULT_BRANCH: Option<&str> = Some("master");
+    /// pub const GIT_REMOTES: [(&str, &str); 1] = [("origin", "https://github.com/lukaslueg/built")];
+    /// pub const GIT_COMMIT_SIGNED: Option<bool> = Some(false);
+    /// pub const GIT_RELATIVE_MANIFEST_DIR: Option<&str> = Some("crates/foobar");
+    /// pub const GIT_STATUS: Option<&str> = None;
+    /// pub const GIT_IS_BARE: Option<bool> = Some(false);
+    /// pub const GIT_IS_WORKTREE: Option<bool> = Some(false);
+    /// ```
+    ///
+    /// Notice that `GIT_HEAD_REF` is `None` if `HEAD` is detached or not valid UTF-8.
+    /// `GIT_DEFAULT_BRANCH` is `None` if there is no `origin`-remote or `origin/HEAD` is not set.
+    /// `GIT_REMOTES` is empty outside of a git-repository.
+    /// `GIT_HEAD_TAGS` is empty if `HEAD` is not tagged; a commit may carry several tags.
+    /// `GIT_COMMITS_SINCE_TAG` is `None` if there is no tag reachable from `HEAD`.
+    ///
+    /// Continuous Integration platforms like `Travis` and `AppVeyor` will
+    /// do shallow clones, causing `libgit2` to be unable to get a meaningful
+    /// result. Errors from `libgit2` never fail the build; `GIT_VERSION` and
+    /// `GIT_DIRTY` will be `None` instead and a warning is emitted.
+    ///
+    #[cfg(feature = "git2")]
+    pub fn set_git(&mut self, enabled: bool) -> &mut Self {
+        self.git = enabled;
+        self
+    }
+
+    /// Detecting and writing the tag or changeset id of the crate's
+    /// Mercurial repository (if any).
+    ///
+    /// This option is only available if `built` was compiled with the
+    /// `hg` feature.
+    ///
+    /// `hg id -i -t` is run at `manifest_location`; the first tag other than
+    /// `tip` is used, the short changeset id otherwise. The result will be
+    /// something like
+    ///
+    /// ```rust,no_run
+    /// pub const HG_VERSION: Option<&str> = Some("0.1");
+    /// ```
+    ///
+    /// `HG_VERSION` is `None` if there is no Mercurial repository or if `hg` is
+    /// not installed. Errors from `hg` never fail the build; a warning is
+    /// emitted instead.
+    #[cfg(feature = "hg")]
+    pub fn set_hg(&mut self, enabled: bool) -> &mut Self {
+        self.hg = enabled;
+        self
+    }
+
+    /// Writing `GIT_VERSION_FIRST_PARENT`, describing HEAD using only the
+    /// first-parent history.
+    ///
+    /// This option is only available if `built` was compiled with the
+    /// `git2` feature. It is disabled by default and has no effect unless
+    /// `set_git()` is enabled as well.
+    ///
+    /// In heavily-merged repositories, `GIT_VERSION` may refer to a tag from a
+    /// merged-in branch. `GIT_VERSION_FIRST_PARENT` is like
+    /// `git describe --tags --first-parent` instead; see
+    /// [`util::get_repo_description_first_parent`][fp].
+    ///
+    /// ```rust,no_run
+    /// pub const GIT_VERSION_FIRST_PARENT: Option<&str> = Some("0.1-2-g18b2eab");
+    /// ```
+    ///
+    /// [fp]: util/fn.get_repo_description_first_parent.html
+    #[cfg(feature = "git2")]
+    pub fn set_git_first_parent(&mut self, enabled: bool) -> &mut Self {
+        self.git_first_parent = enabled;
+        self
+    }
+
+    /// Writing whether HEAD is on a release-branch.
+    ///
+    /// This option is only available if `built` was compiled with the
+    /// `git2` feature. It has no effect unless `set_git()` is enabled as well.
+    ///
+    /// If `patterns` is not empty, the name of HEAD's branch (e.g. `release/1.0`)
+    /// is matched against the patterns, where `*` matches any sequence of
+    /// characters and `?` matches any single character. `GIT_ON_RELEASE_BRANCH`
+    /// is `None` if HEAD is detached or outside of a git-repository.
+    ///
+    /// ```rust,no_run
+    /// let mut options = built::Options::default();
+    /// options.set_release_branch_patterns(vec!["main".to_owned(), "release/*".to_owned()]);
+    /// ```
+    ///
+    /// `built` then writes something like
+    ///
+    /// ```rust,no_run
+    /// pub const GIT_ON_RELEASE_BRANCH: Option<bool> = Some(true);
+    /// ```
+    #[cfg(feature = "git2")]
+    pub fn set_release_branch_patterns(&mut self, patterns: Vec<String>) -> &mut Self {
+        self.release_branch_patterns = patterns;
+        self
+    }
+
+    /// Compiling the git-information only if the crate using it has a feature enabled.
+    ///
+    /// This option is only available if `built` was compiled with the
+    /// `git2` feature. If `feature` is given, the `GIT_*` constants are written
+    /// with a `#[cfg(feature = "...")]`-attribute, as are the fields and methods
+    /// referring to them due to `set_const_fn()` and `set_ext_trait()`. Code
+    /// using the constants needs to be gated by the same feature. `BUILT_JSON`
+    /// is not affected. This option is disabled by default.
+    ///
+    /// ```rust,no_run
+    /// let mut options = built::Options::default();
+    /// options.set_cfg_gate(Some("build-info-git".to_owned()));
+    /// ```
+    ///
+    /// `built` then writes something like
+    ///
+    /// ```rust,no_run
+    /// #[cfg(feature = "build-info-git")]
+    /// pub const GIT_VERSION: Option<&str> = Some("0.1");
+    /// ```
+    #[cfg(feature = "git2")]
+    pub fn set_cfg_gate(&mut self, feature: Option<String>) -> &mut Self {
+        self.cfg_gate = feature;
+        self
+    }
+
+    /// Limiting the length of `GIT_STATUS`.
+    ///
+    /// This option is only available if `built` was compiled with the
+    /// `git2` feature.
+    ///
+    /// The status of the working directory is truncated to at most `max_len`
+    /// bytes. The default is 1024.
+    ///
+    /// ```rust,no_run
+    /// pub const GIT_STATUS: Option<&str> = Some(" M src/lib.rs\n?? new.txt");
+    /// ```
+    #[cfg(feature = "git2")]
+    pub fn set_git_status_max_len(&mut self, max_len: usize) -> &mut Self {
+        self.git_status_max_len = max_len;
+        self
+    }
+
+    /// Detecting and writing the Continuous Integration Platforms we are running on.
+    ///
+    /// Detect various CI-platforms (named or not) and write something like
+    ///
+    /// ```rust
+    /// pub const CI_PLATFORM: Option<&str> = Some("AppVeyor");
+    /// ```
+    pub fn set_ci(&mut self, enabled: bool) -> &mut Self {
+        self.ci = enabled;
+        self
+    }
+
+    /// Detecting various information provided through the environment, especially Cargo.
+    ///
+    /// `built` writes something like
+    ///
+    /// ```rust,no_run
+    /// #[doc="The full version."]
+    /// pub const PKG_VERSION: &str = "1.2.3-rc1";
+    /// #[doc="The major version."]
+    /// pub const PKG_VERSION_MAJOR: &str = "1";
+    /// #[doc="The minor version."]
+    /// pub const PKG_VERSION_MINOR: &str = "2";
+    /// #[doc="The patch version."]
+    /// pub const PKG_VERSION_PATCH: &str = "3";
+    /// #[doc="The pre-release version."]
+    /// pub const PKG_VERSION_PRE: &str = "rc1";
+    /// #[doc="A colon-separated list of authors."]
+    /// pub const PKG_AUTHORS: &str = "Joe:Bob:Harry:Potter";
+    /// #[doc="The name of the package."]
+    /// pub const PKG_NAME: &str = "testbox";
+    /// #[doc="The description."]
+    /// pub const PKG_DESCRIPTION: &str = "xobtset";
+    /// #[doc="The home page."]
+    /// pub const PKG_HOMEPAGE: &str = "localhost";
+    /// #[doc="The minimum supported Rust version as declared by `rust-version`, empty if not set."]
+    /// pub const PKG_RUST_VERSION: &str = "1.56";
+    /// #[doc="The major version, as a number."]
+    /// pub const PKG_VERSION_MAJOR_NUM: u64 = 1;
+    /// #[doc="The minor version, as a number."]
+    /// pub const PKG_VERSION_MINOR_NUM: u64 = 2;
+    /// #[doc="The patch version, as a number."]
+    /// pub const PKG_VERSION_PATCH_NUM: u64 = 3;
+    /// #[doc="The target triple that was being compiled for."]
+    /// pub const TARGET: &str = "x86_64-apple-darwin";
+    /// #[doc="The host triple of the rust compiler."]
+    /// pub const HOST: &str = "x86_64-apple-darwin";
+    /// #[doc="`release` for release builds, `debug` for other builds."]
+    /// pub const PROFILE: &str = "debug";
+    /// #[doc="The compiler that cargo resolved to use."]
+    /// pub const RUSTC: &str = "rustc";
+    /// #[doc="The documentation generator that cargo resolved to use."]
+    /// pub const RUSTDOC: &str = "rustdoc";
+    /// #[doc="The architecture of the host, parsed from `HOST`."]
+    /// pub const HOST_ARCH: &str = "x86_64";
+    /// #[doc="The vendor of the host, parsed from `HOST`."]
+    /// pub const HOST_VENDOR: &str = "apple";
+    /// #[doc="The operating system of the host, parsed from `HOST`."]
+    /// pub const HOST_OS: &str = "darwin";
+    /// #[doc="The toolchain-environment of the host, parsed from `HOST`."]
+    /// pub const HOST_ENV: &str = "";
+    /// #[doc="Value of OPT_LEVEL for the profile used during compilation."]
+    /// pub const OPT_LEVEL: &str = "0";
+    /// #[doc="The parallelism that was specified during compilation."]
+    /// pub const NUM_JOBS: u32 = 8;
+    /// #[doc="Value of DEBUG for the profile used during compilation."]
+    /// pub const DEBUG: bool = true;
+    /// #[doc="If debug-assertions were enabled, given by `CARGO_CFG_DEBUG_ASSERTIONS`."]
+    /// pub const DEBUG_ASSERTIONS: bool = true;
+    /// #[doc="The name of the Cargo profile (e.g. `dev`, `release` or a custom profile), derived from `OUT_DIR`; best-effort."]
+    /// pub const PROFILE_NAME: Option<&str> = Some("dev");
+    /// #[doc="The number of codegen units, if set via `-C codegen-units` in the rustflags."]
+    /// pub const CODEGEN_UNITS: Option<u32> = None;
+    /// #[doc="The codegen backend, like `llvm` or `cranelift`."]
+    /// pub const CODEGEN_BACKEND: &str = "llvm";
+    /// #[doc="If this was a clean build without a populated incremental cache; best-effort."]
+    /// pub const CLEAN_BUILD: Option<bool> = Some(false);
+    /// #[doc="If the target is a WebAssembly-target, given by `CARGO_CFG_TARGET_ARCH`."]
+    /// pub const IS_WASM: bool = false;
+    /// #[doc="The flavor of the WebAssembly-target (`wasi`, `emscripten` or `unknown`), given by `CARGO_CFG_TARGET_OS`."]
+    /// pub const WASM_FLAVOR: Option<&str> = None;
+    /// #[doc="The name of the library-target, if explicitly set in `Cargo.toml`."]
+    /// pub const LIB_NAME: Option<&str> = None;
+    /// #[doc="The edition of the package, as declared in `Cargo.toml`."]
+    /// pub const PKG_EDITION: Option<&str> = Some("2021");
+    /// #[doc="The minimum version of rustc supporting the edition of the package."]
+    /// pub const MIN_EDITION_RUSTC: Option<&str> = Some("1.56");
+    /// #[doc="If the standard library was built from source, given by `CARGO_UNSTABLE_BUILD_STD`."]
+    /// pub const BUILD_STD: bool = false;
+    /// #[doc="The features the standard library was built with from source, given by `CARGO_UNSTABLE_BUILD_STD_FEATURES`."]
+    /// pub const BUILD_STD_FEATURES: [&str; 0] = [];
+    /// ```
+    ///
+    /// Cargo only reports `debug` or `release` as the `PROFILE`, even for
+    /// custom profiles like `[profile.production]`. To tell those apart, the
+    /// profile's name is derived from the layout of the target-directory as
+    /// `PROFILE_NAME`; `OPT_LEVEL`, `DEBUG` and `DEBUG_ASSERTIONS` give the
+    /// profile's effective settings.
+    ///
+    /// The package information can be supplied explicitly using
+    /// [`set_package_metadata()`][spm], which is required for build systems
+    /// other than Cargo.
+    ///
+    /// [spm]: #method.set_package_metadata
+    pub fn set_env(&mut self, enabled: bool) -> &mut Self {
+        self.env = enabled;
+        self
+    }
+
+    /// Parsing `Cargo.lock`and writing lists of dependencies and their versions.
+    ///
+    /// For this to work, `Cargo.lock` needs to actually be there; this is (usually)
+    /// only true for executables and not for libraries. Cargo will only create a
+    /// `Cargo.lock` for the top-level crate in a dependency-tree. In case
+    /// of a library, the top-level crate will decide which crate/version
+    /// combination to compile and there will be no `Cargo.lock` while the library
+    /// gets compiled as a dependency. If there is no `Cargo.lock`, a warning is
+    /// printed and no dependencies are written. For members of a workspace, the
+    /// `Cargo.lock` in the closest parent directory is used.
+    ///
+    /// Parsing `Cargo.lock` instead of `Cargo.toml` allows us to serialize the
+    /// precise versions Cargo chose to compile. One can't, however, distinguish
+    /// `build-dependencies`, `dev-dependencies` and `dependencies`. Furthermore,
+    /// some dependencies never show up if Cargo had not been forced to
+    /// actually use them (e.g. `dev-dependencies` with `cargo test` never
+    /// having been executed).
+    ///
+    /// ```rust,no_run
+    /// /// The format-version of `Cargo.lock`; zero if there is none.
+    /// pub const CARGO_LOCK_VERSION: u32 = 3;
+    /// /// An array of effective dependencies as documented by `Cargo.lock`
+    /// pub const DEPENDENCIES: [(&str, &str); 2] = [("built", "0.1.0"), ("time", "0.1.36")];
+    /// /// The effective dependencies as a comma-separated string.
+    /// pub const DEPENDENCIES_STR: &str = "built 0.1.0, time 0.1.36";
+    /// /// The number of `DEPENDENCIES`.
+    /// pub const DEPENDENCIES_COUNT: usize = 2;
+    /// /// The kind of source of each of the `DEPENDENCIES`: `crates.io`, `registry`, `git` or `path`.
+    /// pub const DEPENDENCIES_SOURCES: [&str; 2] = ["path", "crates.io"];
+    /// /// The direct dependencies as `(name, requirement, resolved version)`, cross-referencing `Cargo.toml` and `Cargo.lock`.
+    /// pub const DEPENDENCY_RESOLUTION: [(&str, &str, &str); 1] = [("time", "0.1", "0.1.36")];
+    /// /// The names of the effective dependencies, grouped by the url of the registry they were retrieved from.
+    /// pub const DEPENDENCIES_BY_REGISTRY: [(&str, &[&str]); 1] = [("https://github.com/rust-lang/crates.io-index", &["time"])];
+    /// ```
+    ///
+    /// `DEPENDENCY_RESOLUTION` only covers the direct dependencies declared in
+    /// `Cargo.toml`, showing where Cargo resolved a requirement to a version
+    /// other than its lower bound.
+    pub fn set_dependencies(&mut self, enabled: bool) -> &mut Self {
+        self.deps = enabled;
+        self
+    }
+
+    /// Writing the dependencies which have been yanked from their registry.
+    ///
+    /// This option only has an effect along with `set_dependencies()` and is
+    /// disabled by default, as the registry-index of every dependency has to
+    /// be read during each build. The information is taken from the copies of
+    /// the registry-indices Cargo keeps below `CARGO_HOME`; nothing is fetched
+    /// from the network. Yanks that happened after Cargo last updated the index
+    /// are therefor not known.
+    ///
+    /// ```rust,no_run
+    /// /// The dependencies (as `name version`) known to be yanked, according to the local copy of the registry-index.
+    /// pub const YANKED_DEPENDENCIES: [&str; 0] = [];
+    /// ```
+    pub fn set_yanked_dependencies(&mut self, enabled: bool) -> &mut Self {
+        self.yanked_deps = enabled;
+        self
+    }
+
+    /// Supplying the package information explicitly.
+    ///
+    /// The given metadata is used for the `PKG_*` constants written due to
+    /// `set_env()`, overriding the `CARGO_PKG_*` environment variables. This
+    /// allows build systems other than Cargo to generate a compatible file.
+    ///
+    /// The build-variables only Cargo provides are not required then: If
+    /// missing, `TARGET`, `HOST`, `PROFILE` and `OPT_LEVEL` are written as
+    /// empty strings, `NUM_JOBS` as zero and `DEBUG` as `false`; `RUSTC` and
+    /// `RUSTDOC` default to `rustc` and `rustdoc` on the `PATH`.
+    /// `Cargo.toml` is not read either, so `LIB_NAME`, `PKG_EDITION` and
+    /// `MIN_EDITION_RUSTC` are `None` and `NON_DEFAULT_FEATURES` and
+    /// `FEATURE_NAMES` are empty. The same holds for any build without a
+    /// `Cargo.toml` in the manifest-directory.
+    ///
+    /// ```rust,no_run
+    /// let mut options = built::Options::default();
+    /// options.set_package_metadata(built::PackageMetadata {
+    ///     name: "foobar".to_owned(),
+    ///     version: "1.2.3".to_owned(),
+    ///     authors: vec!["Joe".to_owned()],
+    ///     ..Default::default()
+    /// });
+    /// ```
+    pub fn set_package_metadata(&mut self, metadata: PackageMetadata) -> &mut Self {
+        self.package_metadata = Some(metadata);
+        self
+    }
+
+    /// Registering an asset the build-script generated into `OUT_DIR`.
+    ///
+    /// The assets registered are written as `GENERATED_ASSETS`, mapping `name`
+    /// to `relative_path` within `OUT_DIR`. A function `generated_asset()`
+    /// resolves a name to the asset's full path at compile time.
+    ///
+    /// ```rust,no_run
+    /// let mut options = built::Options::default();
+    /// options.register_generated_asset("logo", "assets/logo.png");
+    /// ```
+    ///
+    /// `built` writes something like
+    ///
+    /// ```rust,ignore
+    /// /// Assets generated by the build-script, by name and path relative to `OUT_DIR`.
+    /// pub const GENERATED_ASSETS: [(&str, &str); 1] = [("logo", "assets/logo.png")];
+    ///
+    /// /// The full path of the generated asset `name`, if it was registered.
+    /// pub fn generated_asset(name: &str) -> Option<&'static str> {
+    ///     match name {
+    ///         "logo" => Some(concat!(env!("OUT_DIR"), "/", "assets/logo.png")),
+    ///         _ => None,
+    ///     }
+    /// }
+    /// ```
+    pub fn register_generated_asset<N, P>(&mut self, name: N, relative_path: P) -> &mut Self
+    where
+        N: Into<String>,
+        P: Into<String>,
+    {
+        self.generated_assets
+            .push((name.into(), relative_path.into()));
+        self
+    }
+
+    /// Writing features enabled during build.
+    ///
+    /// One should not rely on this besides convenient debug output. If the runtime
+    /// depends on enabled features, use `#[cfg(feature = "foo")]` instead.
+    ///
+    /// Feature names in `FEATURES` are given the way Cargo names the `CARGO_FEATURE_*`
+    /// variables: uppercase and with `-` replaced by `_`. `FEATURE_NAMES` has the
+    /// names as declared in the manifest where possible. Use `built::util::has_feature()`
+    /// to check for a feature by either name.
+    ///
+    /// ```rust,no_run
+    /// /// The features that were enabled during compilation.
+    /// pub const FEATURES: [&str; 2] = ["DEFAULT", "WAYLAND"];
+    /// /// The features as a comma-separated string.
+    /// pub const FEATURES_STR: &str = "DEFAULT, WAYLAND";
+    /// /// The number of `FEATURES`.
+    /// pub const FEATURES_COUNT: usize = 2;
+    /// /// A fingerprint of the set of features, suitable for cache keys.
+    /// pub const FEATURES_HASH: &str = "7db496a957e098a0";
+    /// /// The features that were enabled during compilation, but are not enabled by the `default` feature.
+    /// pub const NON_DEFAULT_FEATURES: [&str; 1] = ["WAYLAND"];
+    /// /// The names of `FEATURES` as declared in the manifest (e.g. `bar-baz` for `BAR_BAZ`), in the same order.
+    /// pub const FEATURE_NAMES: [&str; 2] = ["default", "wayland"];
+    /// ```
+    pub fn set_features(&mut self, enabled: bool) -> &mut Self {
+        self.features = enabled;
+        self
+    }
+
+    /// Writing the current timestamp.
+    ///
+    /// This option is only available if `built` is compiled with the
+    /// `chrono` feature.
+    ///
+    /// If `built` is included as a runtime-dependency, it can parse the
+    /// string-representation into a `time:Tm` with the help
+    /// of `built::util::strptime()`, or `built::util::parse_rfc822()` if
+    /// `chrono` is not available at runtime.
+    ///
+    /// For reproducible builds, the time is taken from the `SOURCE_DATE_EPOCH`
+    /// environment variable (a Unix timestamp) if it is set.
+    ///
+    /// ```rust,no_run
+    /// /// The built-time in RFC822, UTC
+    /// pub const BUILT_TIME_UTC: &str = "Tue, 14 Feb 2017 01:12:35 GMT";
+    /// /// The build time in seconds since the Unix epoch.
+    /// pub const BUILT_TIME_UNIX: i64 = 1487034755;
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn set_time(&mut self, enabled: bool) -> &mut Self {
+        self.time = enabled;
+        self
+    }
+
+    /// Writing the build time at a fixed offset from UTC as well.
+    ///
+    /// This option is only available if `built` is compiled with the
+    /// `chrono` feature. If `set_time()` is enabled, the build time is
+    /// additionally written at an offset of `offset_seconds` east of UTC,
+    /// e.g. `19800` for `+05:30`; `BUILT_TIME_UTC` stays as it is. Offsets of
+    /// a day or more are ignored. This option is disabled by default.
+    ///
+    /// ```rust,no_run
+    /// /// The build time in RFC2822, at a fixed offset from UTC.
+    /// pub const BUILT_TIME_TZ: &str = "Tue, 14 Feb 2017 06:42:35 +0530";
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn set_time_zone(&mut self, offset_seconds: i32) -> &mut Self {
+        self.time_zone = chrono::FixedOffset::east_opt(offset_seconds);
+        self
+    }
+
+    /// Writing a function that warns about a stale build at runtime.
+    ///
+    /// This option is only available if `built` is compiled with the
+    /// `chrono` feature and has no effect unless `set_time()` is enabled as well.
+    /// It is disabled by default.
+    ///
+    /// The generated function `warn_if_stale(max_days: i64) -> bool` prints a
+    /// warning referring to `BUILT_TIME_UTC` to stderr and returns `true` if the
+    /// binary was built more than `max_days` days ago.
+    ///
+    /// ```rust,no_run
+    /// # mod built_info { pub fn warn_if_stale(max_days: i64) -> bool { false } }
+    /// fn main() {
+    ///     built_info::warn_if_stale(90);
+    /// }
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn set_stale_check(&mut self, enabled: bool) -> &mut Self {
+        self.stale_check = enabled;
+        self
+    }
+
+    /// Writing a heuristic whether this is a release-build.
+    ///
+    /// This option is only available if `built` was compiled with the
+    /// `git2` feature. It is disabled by default.
+    ///
+    /// `LIKELY_RELEASE_BUILD` is `true` if all of the given conditions hold
+    /// during compilation; see [`ReleaseConditions`][rc] for their meaning.
+    /// By default, all conditions are required: The git-repository is clean,
+    /// HEAD is tagged, the crate is not compiled as a path-dependency and
+    /// a CI-platform was detected.
+    ///
+    /// ```rust,no_run
+    /// pub const LIKELY_RELEASE_BUILD: bool = false;
+    /// ```
+    ///
+    /// [rc]: struct.ReleaseConditions.html
+    #[cfg(feature = "git2")]
+    pub fn set_likely_release_build(
+        &mut self,
+        enabled: bool,
+        conditions: ReleaseConditions,
+    ) -> &mut Self {
+        self.likely_release_build = enabled;
+        self.release_conditions = conditions;
+        self
+    }
+
+    /// Writing all information as a single JSON-object.
+    ///
+    /// This option is disabled by default. `BUILT_JSON` contains all of the
+    /// information written due to the other options as a JSON-object, keyed by
+    /// the names of the constants (without prefix). Tuples are written as
+    /// arrays and `None` as `null`. This allows to provide a machine-readable
+    /// snapshot (e.g. for `--version --json`) without assembling it at runtime.
+    /// As the document is embedded into the binary as a string-literal, it can
+    /// also be served as-is, e.g. as the body of a HTTP-response.
+    ///
+    /// ```rust,no_run
+    /// /// All of the information above, as a JSON-object.
+    /// pub const BUILT_JSON: &str = "{\"PKG_VERSION\":\"1.2.3\",\"DEBUG\":true}";
+    /// ```
+    pub fn set_json(&mut self, enabled: bool) -> &mut Self {
+        self.json = enabled;
+        self
+    }
+
+    /// Writing the endianness as a typed enum.
+    ///
+    /// This option is disabled by default. In addition to `CFG_ENDIAN` as
+    /// written by `set_cfg()`, a self-contained enum is emitted, which allows
+    /// to `match` on the endianness. Nothing is written if the endianness is
+    /// unknown.
+    ///
+    /// ```rust,no_run
+    /// /// The endianness of the target.
+    /// #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    /// pub enum Endianness {
+    ///     /// Little endian.
+    ///     Little,
+    ///     /// Big endian.
+    ///     Big,
+    /// }
+    /// /// The endianness as an enum, given by `CARGO_CFG_TARGET_ENDIAN`.
+    /// pub const ENDIANNESS: Endianness = Endianness::Little;
+    /// ```
+    pub fn set_endianness_enum(&mut self, enabled: bool) -> &mut Self {
+        self.endianness_enum = enabled;
+        self
+    }
+
+    /// Writing the configuration attributes.
+    ///
+    /// `built` writes something like
+    ///
+    /// ```rust,no_run
+    /// /// The target architecture, given by `CARGO_CFG_TARGET_ARCH`.
+    /// pub const CFG_TARGET_ARCH: &str = "x86_64";
+    /// /// The endianness, given by `CARGO_CFG_TARGET_ENDIAN`.
+    /// pub const CFG_ENDIAN: &str = "little";
+    /// /// The toolchain-environment, given by `CARGO_CFG_TARGET_ENV`.
+    /// pub const CFG_ENV: &str = "gnu";
+    /// /// The OS-family, given by `CARGO_CFG_TARGET_FAMILY`.
+    /// pub const CFG_FAMILY: &str = "unix";
+    /// /// The operating system, given by `CARGO_CFG_TARGET_OS`.
+    /// pub const CFG_OS: &str = "linux";
+    /// /// The pointer width in bits, given by `CARGO_CFG_TARGET_POINTER_WIDTH`; zero if unknown.
+    /// pub const CFG_POINTER_WIDTH: u8 = 64;
+    /// /// The target vendor, given by `CARGO_CFG_TARGET_VENDOR`.
+    /// pub const CFG_TARGET_VENDOR: &str = "unknown";
+    /// ```
+    pub fn set_cfg(&mut self, enabled: bool) -> &mut Self {
+        self.cfg = enabled;
+        self
+    }
+
+    /// Writing a hash of the contents of the crate's source files.
+    ///
+    /// If the crate is in a git-repository (and `built` was compiled with the
+    /// `git2` feature), the hash covers all files tracked by git below the
+    /// manifest; otherwise it covers all files below `src/`. The files are
+    /// hashed in order of their paths, so identical trees produce the same hash.
+    /// The hash is not cryptographic and should not be relied upon to detect
+    /// deliberate tampering. This option is disabled by default.
+    ///
+    /// ```rust,no_run
+    /// /// A hash of the contents of the crate's source files.
+    /// pub const SOURCE_TREE_HASH: &str = "8c5c0a9b2d4e7f61";
+    /// ```
+    pub fn set_source_hash(&mut self, enabled: bool) -> &mut Self {
+        self.source_hash = enabled;
+        self
+    }
+
+    /// Writing a `const fn` that returns all information as a single struct.
+    ///
+    /// The struct `BuiltInfo` has one field per constant written; since it only
+    /// consists of constants, `built_info()` can be used in other constant
+    /// expressions, like initializing a `static`. This option is disabled by default.
+    ///
+    /// ```rust,no_run
+    /// /// All of the information above, in a single value.
+    /// #[derive(Clone, Copy, Debug)]
+    /// pub struct BuiltInfo {
+    ///     /// The full version.
+    ///     pub pkg_version: &'static str,
+    ///     // ...
+    /// }
+    /// # pub const PKG_VERSION: &str = "";
+    ///
+    /// /// All of the information above, in a single value.
+    /// pub const fn built_info() -> BuiltInfo {
+    ///     BuiltInfo {
+    ///         pkg_version: PKG_VERSION,
+    ///         // ...
+    ///     }
+    /// }
+    /// ```
+    pub fn set_const_fn(&mut self, enabled: bool) -> &mut Self {
+        self.const_fn = enabled;
+        self
+    }
+
+    /// Writing a constant holding all information as a single struct.
+    ///
+    /// Like `set_const_fn()`, the struct `BuiltInfo` has one field per constant
+    /// written, using only types from the standard library. The constant `INFO`
+    /// allows to pass around all information as one value. This option is
+    /// disabled by default.
+    ///
+    /// ```rust,no_run
+    /// /// All of the information above, in a single value.
+    /// #[derive(Clone, Copy, Debug)]
+    /// pub struct BuiltInfo {
+    ///     /// The full version.
+    ///     pub pkg_version: &'static str,
+    ///     // ...
+    /// }
+    /// # pub const PKG_VERSION: &str = "";
+    ///
+    /// /// All of the information above, in a single value.
+    /// pub const INFO: BuiltInfo = BuiltInfo {
+    ///     pkg_version: PKG_VERSION,
+    ///     // ...
+    /// };
+    /// ```
+    pub fn set_struct(&mut self, enabled: bool) -> &mut Self {
+        self.info_struct = enabled;
+        self
+    }
+
+    /// Writing a trait with one method per constant, implemented for a zero-sized type.
+    ///
+    /// This is an alternative to accessing the constants directly: The trait
+    /// `BuiltInfoExt` can be imported and its methods called on `BuildInfo`;
+    /// only the constants written before it are covered. This option is disabled
+    /// by default.
+    ///
+    /// ```rust,no_run
+    /// /// Access to all of the information above by method.
+    /// pub trait BuiltInfoExt {
+    ///     /// The full version.
+    ///     fn pkg_version(&self) -> &'static str;
+    ///     // ...
+    /// }
+    ///
+    /// /// A zero-sized type implementing `BuiltInfoExt`.
+    /// #[derive(Clone, Copy, Debug, Default)]
+    /// pub struct BuildInfo;
+    /// # pub const PKG_VERSION: &str = "";
+    ///
+    /// impl BuiltInfoExt for BuildInfo {
+    ///     fn pkg_version(&self) -> &'static str {
+    ///         PKG_VERSION
+    ///     }
+    ///     // ...
+    /// }
+    /// ```
+    pub fn set_ext_trait(&mut self, enabled: bool) -> &mut Self {
+        self.ext_trait = enabled;
+        self
+    }
+
+    /// Detecting and writing whether authentication for a private registry was configured.
+    ///
+    /// Authentication is detected by the presence of a `CARGO_REGISTRIES_<NAME>_TOKEN`
+    /// environment variable or a token for one of the `[registries]` in Cargo's
+    /// credentials-file. The tokens themselves are never written. This option is
+    /// disabled by default.
+    ///
+    /// ```rust,no_run
+    /// /// If authentication for a private registry was configured during compilation.
+    /// pub const PRIVATE_REGISTRY_AUTH_PRESENT: bool = false;
+    /// ```
+    pub fn set_registry_auth(&mut self, enabled: bool) -> &mut Self {
+        self.registry_auth = enabled;
+        self
+    }
+
+    /// Writing how long gathering the information took.
+    ///
+    /// This helps to decide which options to disable if `built` slows down the
+    /// build; calling the compiler to get its version usually takes the most
+    /// time. Since the value differs on every build, this option is disabled
+    /// by default.
+    ///
+    /// ```rust,no_run
+    /// /// The time it took `built` to gather the information above, in milliseconds.
+    /// pub const BUILT_GATHER_DURATION_MS: u64 = 42;
+    /// ```
+    pub fn set_gather_duration(&mut self, enabled: bool) -> &mut Self {
+        self.gather_duration = enabled;
+        self
+    }
+
+    /// Writing the build-relevant `RUST_*` environment variables.
+    ///
+    /// Variables like `RUST_MIN_STACK` affect the compiler itself, e.g. for
+    /// crates heavy on procedural macros. Only variables that were actually set
+    /// are written. This option is disabled by default.
+    ///
+    /// ```rust,no_run
+    /// /// The build-relevant `RUST_*` environment variables that were set during compilation.
+    /// pub const BUILD_RUST_ENV: [(&str, &str); 1] = [("RUST_BACKTRACE", "1")];
+    /// ```
+    pub fn set_rust_env(&mut self, enabled: bool) -> &mut Self {
+        self.rust_env = enabled;
+        self
+    }
+
+    /// Writing a function `pkg_version()`, returning the package's version as
+    /// a `semver::Version`.
+    ///
+    /// This option is only available if `built` was compiled with the `semver`
+    /// feature. The version is split and validated at build-time. Since
+    /// `semver` can't construct them in a `const`-context, non-empty
+    /// pre-release identifiers and build-metadata are still parsed at runtime,
+    /// every time `pkg_version()` is called. This option is disabled by
+    /// default.
+    ///
+    /// The generated code refers to `built::semver`. The crate therefor needs
+    /// `built` with the `semver` feature as a regular dependency in
+    /// `[dependencies]`, not only in `[build-dependencies]`.
+    ///
+    /// ```rust,no_run
+    /// /// The version of the package, as a `semver::Version`.
+    /// pub fn pkg_version() -> built::semver::Version {
+    ///     built::semver::Version {
+    ///         major: 1,
+    ///         minor: 2,
+    ///         patch: 3,
+    ///         pre: built::semver::Prerelease::new("rc1")
+    ///             .expect("The pre-release was validated at build-time"),
+    ///         build: built::semver::BuildMetadata::EMPTY,
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "semver")]
+    pub fn set_pkg_version_fn(&mut self, enabled: bool) -> &mut Self {
+        self.pkg_version_fn = enabled;
+        self
+    }
+
+    /// Writing the operating system, architecture and kernel of the machine
+    /// that ran the build.
+    ///
+    /// These describe the machine executing the build-script, not the target
+    /// the crate is compiled for; the kernel is retrieved via `uname` on unix
+    /// and is `None` elsewhere. Since this identifies the build-machine to some
+    /// degree, this option is disabled by default.
+    ///
+    /// ```rust,no_run
+    /// /// The operating system of the machine that ran the build, as given by `std::env::consts::OS`.
+    /// pub const BUILD_OS: &str = "linux";
+    /// /// The architecture of the machine that ran the build, as given by `std::env::consts::ARCH`.
+    /// pub const BUILD_ARCH: &str = "x86_64";
+    /// /// The kernel of the machine that ran the build, as given by `uname -sr`; `None` if unavailable.
+    /// pub const BUILD_KERNEL: Option<&str> = Some("Linux 6.1.0-18-amd64");
+    /// ```
+    pub fn set_build_host(&mut self, enabled: bool) -> &mut Self {
+        self.build_host = enabled;
+        self
+    }
+
+    /// Making the emitted items public.
+    ///
+    /// Public items are documented, so they satisfy `#![deny(missing_docs)]`.
+    /// If disabled, items are emitted without `pub` and without documentation;
+    /// the generated file should then be `include!`-ed directly into the module
+    /// that uses it, as its items are not visible from outside of that module.
+    /// The default is to emit public items.
+    ///
+    /// ```rust,no_run
+    /// const PKG_VERSION: &str = "1.2.3-rc1";
+    /// ```
+    pub fn set_public(&mut self, enabled: bool) -> &mut Self {
+        self.public = enabled;
+        self
+    }
+
+    /// Prefixing every emitted identifier.
+    ///
+    /// This avoids name-clashes if the generated code is concatenated with code
+    /// from other generators, or if multiple generated files are `include!`-ed
+    /// into the same module. Constants are prefixed as given, functions with the
+    /// lowercase prefix and types with the CamelCase prefix. The default is no
+    /// prefix.
+    ///
+    /// The emitted `#[doc=...]` attributes are not changed: They keep referring
+    /// to environment variables and to other constants (e.g. `DEPENDENCIES`)
+    /// by their unprefixed names.
+    ///
+    /// With a prefix of `BUILT_`, `built` writes something like
+    ///
+    /// ```rust,no_run
+    /// #[doc="The full version."]
+    /// pub const BUILT_PKG_VERSION: &str = "1.2.3-rc1";
+    /// ```
+    pub fn set_name_prefix(&mut self, prefix: impl Into<String>) -> &mut Self {
+        self.name_prefix = prefix.into();
+        self
+    }
+
+    /// Registering the files the information depends on via `cargo:rerun-if-changed`.
+    ///
+    /// This option is enabled by default. Without it, Cargo does not know to
+    /// rerun the build-script if `Cargo.lock` or the git-repository changes,
+    /// leaving e.g. an outdated `GIT_COMMIT_HASH`. If dependencies or
+    /// git-information are written, `write_built_file_with_opts()` prints a
+    /// `cargo:rerun-if-changed` directive for `Cargo.toml`, `Cargo.lock` (if
+    /// dependencies are written) and the files of the git-repository which
+    /// change with `HEAD`, like `.git/HEAD` and `.git/packed-refs` (if
+    /// git-information is written).
+    ///
+    /// Once a build-script emits any `rerun-if-changed`-directive, Cargo only
+    /// reruns it if one of the registered files changes. To keep rerunning if
+    /// any of the package's files changes, like Cargo does by default, the
+    /// top-level files and directories of the package (like `src`, but not
+    /// hidden ones like `.git` and not the target-directory) are registered as
+    /// well; Cargo scans directories recursively. This keeps information like
+    /// `GIT_DIRTY` or `SOURCE_TREE_HASH` and the build-script's own inputs
+    /// up to date. The same list is written as `REBUILD_TRIGGERS`, so the
+    /// binary can tell what its build-information depends on.
+    ///
+    /// Along with these files, the variables `built` reads from the environment
+    /// Cargo was invoked in, like the ones used to detect a CI-platform and
+    /// `SOURCE_DATE_EPOCH`, are registered via `cargo:rerun-if-env-changed`.
+    /// The variables Cargo sets for the build-script itself, like `PROFILE` or
+    /// `CARGO_FEATURE_*`, need no registration; Cargo reruns the build-script
+    /// if they change anyway.
+    ///
+    /// Notice that files outside of the package, and new top-level files, are
+    /// not covered; such inputs of the build-script need to be registered by
+    /// the build-script itself. Disable this option to keep Cargo's default
+    /// behaviour.
+    ///
+    /// ```rust,no_run
+    /// /// The files registered via `cargo:rerun-if-changed`, the information above depends on.
+    /// pub const REBUILD_TRIGGERS: [&str; 5] = ["Cargo.toml", "Cargo.lock", "/home/joe/foobar/.git/HEAD", "build.rs", "src"];
+    /// ```
+    pub fn set_rerun_if_changed(&mut self, enabled: bool) -> &mut Self {
+        self.rerun_if_changed = enabled;
+        self
+    }
+
+    /// Overwriting an existing destination file.
+    ///
+    /// If disabled, `write_built_file_with_opts()` returns an error if the file
+    /// at `dst` already exists, instead of truncating it. The default is to
+    /// overwrite the file.
+    ///
+    /// ```rust,no_run
+    /// let mut options = built::Options::default();
+    /// options.set_overwrite(false);
+    /// ```
+    pub fn set_overwrite(&mut self, enabled: bool) -> &mut Self {
+        self.overwrite = enabled;
+        self
+    }
+
+    /// Transforming the generated code before it is written.
+    ///
+    /// The function receives the complete generated code and returns what is
+    /// actually written to the file, e.g. to prepend a license header or to wrap
+    /// everything in conditional compilation.
+    ///
+    /// ```rust,no_run
+    /// let mut options = built::Options::default();
+    /// options.set_postprocess(|code| format!("// Copyright (c) Foobar Inc.\n{}", code));
+    /// ```
+    pub fn set_postprocess<F>(&mut self, postprocess: F) -> &mut Self
+    where
+        F: Fn(String) -> String + 'static,
+    {
+        self.postprocess = Some(Box::new(postprocess));
+        self
+    }
+
+    /// Reporting progress while the information is gathered.
+    ///
+    /// The function is called with a label as each enabled phase completes,
+    /// in the order they are executed: `ci`, `env`, `features`, `registry_auth`,
+    /// `rust_env`, `compiler`, `git`, `likely_release_build`, `build_host`, `hg`,
+    /// `dependencies`, `time`, `cfg` and `source_hash`. This allows build-scripts
+    /// to find out which phase is slow, e.g. by logging the time it took.
+    ///
+    /// ```rust,no_run
+    /// let start = std::time::Instant::now();
+    /// let mut options = built::Options::default();
+    /// options.set_progress(move |phase| {
+    ///     println!("cargo:warning={} done after {:?}", phase, start.elapsed());
+    /// });
+    /// ```
+    pub fn set_progress<F>(&mut self, progress: F) -> &mut Self
+    where
+        F: Fn(&str) + 'static,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+}
+
+/// Writes all constants selected by `options` to `built_file`.
+///
+/// The information is taken from the environment in `envmap`. The
+/// target-specific information is taken from the `CARGO_CFG_*` variables in
+/// `cfg`; it is not written at all if `cfg` is `None`. The `triggers` are
+/// those of `get_rebuild_triggers()`.
+fn write_built_items(
+    options: &Options,
+    manifest_location: &path::Path,
+    envmap: &EnvironmentMap,
+    cfg: Option<&EnvironmentMap>,
+    triggers: &[String],
+    built_file: &mut CodeWriter,
+) -> Result<(), Error> {
+    let progress = |phase: &str| {
+        if let Some(progress) = &options.progress {
+            progress(phase);
+        }
+    };
+    macro_rules! o {
+        ($i:ident, $b:stmt) => {
+            if options.$i {
+                $b
+            }
+        };
+        ($i:ident, $b:stmt, $phase:expr) => {
+            if options.$i {
+                $b
+                progress($phase);
+            }
+        };
+    }
+    let start = time::Instant::now();
+    if options.ci
+        || options.env
+        || options.features
+        || options.compiler
+        || options.registry_auth
+        || options.rust_env
+        || options.likely_release_build
+        || options.pkg_version_fn
+    {
+        check_environment(env::vars_os())?;
+        let mut envmap = envmap.clone();
+        if let Some(metadata) = &options.package_metadata {
+            metadata.apply(&mut envmap);
+        }
+        // Explicit package metadata replaces the manifest
+        let manifest = if options.package_metadata.is_none() && (options.env || options.features) {
+            find_manifest(manifest_location)?
+        } else {
+            None
+        };
+        o!(ci, write_ci(&envmap, built_file)?, "ci");
+        o!(env, write_env(&envmap, built_file)?);
+        o!(
+            env,
+            write_manifest_info(manifest_location, manifest.as_ref(), built_file)?
+        );
+        o!(env, write_build_std(&envmap, built_file)?, "env");
+        #[cfg(feature = "semver")]
+        o!(pkg_version_fn, write_pkg_version_fn(&envmap, built_file)?);
+        o!(features, write_features(&envmap, built_file)?);
+        o!(
+            features,
+            write_non_default_features(&envmap, manifest.as_ref(), built_file)?,
+            "features"
+        );
+        o!(
+            registry_auth,
+            write_registry_auth(&envmap, built_file)?,
+            "registry_auth"
+        );
+        o!(rust_env, write_rust_env(&envmap, built_file)?, "rust_env");
+        o!(
+            compiler,
+            write_compiler_version(
+                get_env_var(&envmap, "RUSTC")?.as_ref(),
+                get_env_var(&envmap, "RUSTDOC")?.as_ref(),
+                built_file
+            )?,
+            "compiler"
+        );
+        #[cfg(feature = "git2")]
+        {
+            built_file.gate.clone_from(&options.cfg_gate);
+            o!(
+                git,
+                write_git_version(
+                    manifest_location,
+                    options.git_status_max_len,
+                    options.git_first_parent,
+                    &options.release_branch_patterns,
+                    built_file
+                )?,
+                "git"
+            );
+            built_file.gate = None;
+            o!(
+                likely_release_build,
+                write_likely_release_build(
+                    &envmap,
+                    manifest_location,
+                    &options.release_conditions,
+                    built_file
+                )?,
+                "likely_release_build"
+            );
+        }
+    }
+    o!(build_host, write_build_host(built_file)?, "build_host");
+    #[cfg(feature = "hg")]
+    o!(hg, write_hg_version(manifest_location, built_file)?, "hg");
+    o!(
+        deps,
+        write_dependencies(envmap, manifest_location, options.yanked_deps, built_file)?,
+        "dependencies"
+    );
+    #[cfg(feature = "chrono")]
+    {
+        let now = get_build_time(envmap);
+        if options.time {
+            write_time(&now, options.time_zone, built_file)?;
+            o!(stale_check, write_stale_check(&now, built_file)?);
+            progress("time");
+        }
+    }
+    if let Some(cfg) = cfg {
+        o!(cfg, write_cfg(cfg, built_file)?, "cfg");
+        o!(endianness_enum, write_target_endianness(cfg, built_file)?);
+    }
+    o!(
+        source_hash,
+        write_source_hash(manifest_location, built_file)?,
+        "source_hash"
+    );
+    if !options.generated_assets.is_empty() {
+        write_generated_assets(&options.generated_assets, built_file)?;
+    }
+    o!(
+        gather_duration,
+        write_gather_duration(start.elapsed(), built_file)?
+    );
+    if !triggers.is_empty() {
+        write_rebuild_triggers(triggers, built_file)?;
+    }
+    o!(json, write_json(built_file)?);
+    if options.const_fn || options.info_struct {
+        write_info_struct(options.const_fn, options.info_struct, built_file)?;
+    }
+    o!(ext_trait, write_ext_trait(built_file)?);
+    Ok(())
+}
+
+/// Writes rust-code describing the crate at `manifest_location` to a file named `dst`.
+///
+/// An existing file is overwritten, unless disabled via `Options::set_overwrite()`.
+/// The files and environment variables the information depends on are
+/// registered with Cargo via `cargo:rerun-if-changed` and
+/// `cargo:rerun-if-env-changed`, see `Options::set_rerun_if_changed()`.
+///
+/// # Errors
+/// The function returns an error if the file at `dst` can't be written to or
+/// already exists while overwriting is disabled, or if acquiring the
+/// information failed, e.g. because `Cargo.toml` could not be parsed or an
+/// environment variable expected to be set by Cargo is missing.
+///
+/// The variables `TARGET`, `HOST`, `PROFILE`, `RUSTC`, `RUSTDOC`, `OPT_LEVEL`,
+/// `NUM_JOBS` and `DEBUG` are required and must be valid UTF-8; otherwise
+/// `Error::InvalidEnv` is returned. Other variables that are not valid UTF-8,
+/// e.g. a `CARGO_PKG_DESCRIPTION` on some Windows-setups, are converted lossily.
+pub fn write_built_file_with_opts(
+    options: &Options,
+    manifest_location: &path::Path,
+    dst: &path::Path,
+) -> Result<(), Error> {
+    let triggers = get_rebuild_triggers(options, manifest_location);
+    let mut code = Vec::new();
+    write_built_info_from(
+        options,
+        manifest_location,
+        &get_environment(),
+        &triggers,
+        &mut code,
+    )?;
+    for trigger in &triggers {
+        println!("cargo:rerun-if-changed={}", trigger);
+    }
+    // Without any file registered, Cargo's default of rerunning on any
+    // change is kept; registering the variables would disable it.
+    if !triggers.is_empty() {
+        for var in get_rerun_env_vars(options) {
+            println!("cargo:rerun-if-env-changed={}", var);
+        }
+    }
+    let mut file = if options.overwrite {
+        fs::File::create(dst)?
+    } else {
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dst)?
+    };
+    file.write_all(&code)?;
+    Ok(())
+}
+
+/// Writes rust-code describing the crate at `manifest_location` to `w`.
+///
+/// This is what `write_built_file_with_opts()` writes to its file; the code can
+/// be written to any destination instead, e.g. a `Vec<u8>`.
+///
+/// ```rust,no_run
+/// let src = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+/// let mut code = Vec::new();
+/// built::write_built_info(&built::Options::default(), src.as_ref(), &mut code).unwrap();
+/// assert!(String::from_utf8(code).unwrap().contains("PKG_VERSION"));
+/// ```
+///
+/// # Errors
+/// Same as `write_built_file_with_opts()`, or if writing to `w` fails.
+pub fn write_built_info<W: io::Write>(
+    options: &Options,
+    manifest_location: &path::Path,
+    w: &mut W,
+) -> Result<(), Error> {
+    write_built_info_from(
+        options,
+        manifest_location,
+        &get_environment(),
+        &get_rebuild_triggers(options, manifest_location),
+        w,
+    )
+}
+
+/// Writes rust-code like `write_built_info()`, taking the information from
+/// `envmap` and the given `triggers`.
+fn write_built_info_from<W: io::Write>(
+    options: &Options,
+    manifest_location: &path::Path,
+    envmap: &EnvironmentMap,
+    triggers: &[String],
+    w: &mut W,
+) -> Result<(), Error> {
+    let mut code = Vec::new();
+    let mut built_file = CodeWriter::new(&mut code, &options.name_prefix);
+    built_file.public = options.public;
+    built_file.write_all(
+        r#"//
+// EVERYTHING BELOW THIS POINT WAS AUTO-GENERATED DURING COMPILATION. DO NOT MODIFY.
+//
+"#
+        .as_ref(),
+    )?;
+
+    write_built_items(
+        options,
+        manifest_location,
+        envmap,
+        Some(envmap),
+        triggers,
+        &mut built_file,
+    )?;
+    built_file.write_all(
+        r#"//
+// EVERYTHING ABOVE THIS POINT WAS AUTO-GENERATED DURING COMPILATION. DO NOT MODIFY.
+//
+"#
+        .as_ref(),
+    )?;
+
+    let code = String::from_utf8(code)?;
+    let code = match options.postprocess {
+        Some(ref postprocess) => postprocess(code),
+        None => code,
+    };
+    w.write_all(code.as_bytes())?;
+    Ok(())
+}
+
+/// Gathers the information selected by `options` as a JSON-object.
+///
+/// This function is only available if `built` was compiled with the `serde`
+/// feature. It allows build-scripts that already use `serde` to inspect or
+/// manipulate the information. The object's keys are the names of the constants
+/// (without prefix); their values are the same as in the generated code, with
+/// tuples written as arrays and `None` as `null`. `serde` is never required by
+/// the code generated via `write_built_file()`.
+///
+/// ```rust,no_run
+/// let src = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+/// let info = built::gather_as_json_value(&built::Options::default(), src.as_ref()).unwrap();
+/// assert!(info["PKG_VERSION"].is_string());
+/// ```
+///
+/// # Errors
+/// Same as `write_built_file_with_opts()`, or `Error::Json` if the information
+/// could not be converted.
+#[cfg(feature = "serde")]
+pub fn gather_as_json_value(
+    options: &Options,
+    manifest_location: &path::Path,
+) -> Result<serde_json::Value, Error> {
+    let info = gather(options, manifest_location)?;
+    Ok(serde_json::from_str(&items_to_json(&info.items))?)
+}
+
+/// The constants selected by some `Options`, as gathered by `gather()`.
+pub struct GatheredInfo {
+    prefix: String,
+    public: bool,
+    items: Vec<Item>,
+}
+
+/// Gathers the constants selected by `options`, without writing any code.
+///
+/// The result can be compared to a previously generated file using `diff_built()`.
+///
+/// # Errors
+/// Same as `write_built_file_with_opts()`.
+pub fn gather(options: &Options, manifest_location: &path::Path) -> Result<GatheredInfo, Error> {
+    let mut sink = io::sink();
+    let mut built_file = CodeWriter::new(&mut sink, &options.name_prefix);
+    built_file.public = options.public;
+    let envmap = get_environment();
+    write_built_items(
+        options,
+        manifest_location,
+        &envmap,
+        Some(&envmap),
+        &get_rebuild_triggers(options, manifest_location),
+        &mut built_file,
+    )?;
+    Ok(GatheredInfo {
+        prefix: options.name_prefix.clone(),
+        public: options.public,
+        items: built_file.items,
+    })
+}
+
+/// The types and values of the constants in a file generated by `built`, by their emitted name.
+fn parse_consts(src: &str) -> collections::HashMap<&str, (&str, &str)> {
+    src.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix("pub ").unwrap_or(line);
+            let (name, rest) = line.strip_prefix("const ")?.split_once(": ")?;
+            let (datatype, value) = rest.split_once(" = ")?;
+            Some((name, (datatype, value.strip_suffix(';')?)))
+        })
+        .collect()
+}
+
+/// Writes the constants in `new` whose values differ from those in `old_src`.
+///
+/// `old_src` is the content of a file previously generated by `built`, using the
+/// same name-prefix. Constants that are not present in `old_src` are also
+/// written; constants that are no longer present in `new` are ignored. All other
+/// code, like the `const fn` or the stale-check, is never written.
+///
+/// ```rust,no_run
+/// let src = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+/// let old_src = std::fs::read_to_string("built.rs").unwrap();
+/// let new = built::gather(&built::Options::default(), src.as_ref()).unwrap();
+/// print!("{}", built::diff_built(&old_src, new));
+/// ```
+pub fn diff_built(old_src: &str, new: GatheredInfo) -> String {
+    let old = parse_consts(old_src);
+    let mut sink = io::sink();
+    let mut w = CodeWriter::new(&mut sink, &new.prefix);
+    w.public = new.public;
+    let mut code = String::new();
+    for item in &new.items {
+        let name = w.name(&item.name);
+        w.gate.clone_from(&item.gate);
+        if old.get(name.as_str()).map(|(_, value)| *value) != Some(item.value.as_str()) {
+            code.push_str(&format!(
+                "{}const {}: {} = {};\n",
+                w.header(&item.doc),
+                name,
+                item.datatype,
+                item.value
+            ));
+        }
+    }
+    code
+}
+
+/// The `cfg` of `target` as given by `rustc --print cfg`, as `CARGO_CFG_*` variables.
+///
+/// Like Cargo, the values of keys given multiple times are joined by commas.
+fn get_target_cfg(rustc: &ffi::OsStr, target: &str) -> Result<EnvironmentMap, Error> {
+    let output = process::Command::new(rustc)
+        .args(["--print", "cfg", "--target", target])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).into_owned()).into());
+    }
+    Ok(parse_target_cfg(&String::from_utf8(output.stdout)?))
+}
+
+fn parse_target_cfg(cfg: &str) -> EnvironmentMap {
+    let mut envmap = EnvironmentMap::new();
+    for line in cfg.lines() {
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key, value.trim_matches('"')),
+            None => (line, ""),
+        };
+        let key = format!("CARGO_CFG_{}", key.to_uppercase());
+        match envmap.get_mut(&key) {
+            Some(values) if !value.is_empty() => {
+                values.push(',');
+                values.push_str(value);
+            }
+            Some(_) => {}
+            None => {
+                envmap.insert(key, value.to_owned());
+            }
+        }
+    }
+    envmap
+}
+
+/// The name of the module holding the information about `target`.
+fn target_mod_name(target: &str) -> String {
+    target
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Gathers the information selected by `options` for several targets at once,
+/// returning the generated code.
+///
+/// This is useful for builds combining multiple targets, like macOS universal
+/// binaries. Information that does not depend on the target is written once;
+/// the `CFG_*` constants (and `ENDIANNESS`, if enabled) are written to a module
+/// per target, named after the target triple's identifier, e.g.
+/// `aarch64_apple_darwin`. The triples are written as `TARGETS`. The `cfg` of each target is given by `rustc --print cfg`.
+///
+/// ```rust,no_run
+/// let src = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+/// let code = built::gather_for_targets(
+///     &["x86_64-apple-darwin", "aarch64-apple-darwin"],
+///     &built::Options::default(),
+///     src.as_ref(),
+/// )
+/// .unwrap();
+/// let dst = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("built.rs");
+/// std::fs::write(dst, code).unwrap();
+/// ```
+///
+/// # Errors
+/// Same as `write_built_file_with_opts()`, or if `rustc` fails to give the `cfg`
+/// of one of the `targets`.
+pub fn gather_for_targets(
+    targets: &[&str],
+    options: &Options,
+    manifest_location: &path::Path,
+) -> Result<String, Error> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let mut code = Vec::new();
+    let mut built_file = CodeWriter::new(&mut code, &options.name_prefix);
+    built_file.public = options.public;
+    write_built_items(
+        options,
+        manifest_location,
+        &get_environment(),
+        None,
+        &get_rebuild_triggers(options, manifest_location),
+        &mut built_file,
+    )?;
+    write_variable!(
+        built_file,
+        "TARGETS",
+        format!("[&str; {}]", targets.len()),
+        format!("{:?}", targets),
+        "The target triples information is available for, each in a module of its own."
+    );
+
+    for target in targets {
+        let cfg = get_target_cfg(&rustc, target)?;
+        let mut target_file = CodeWriter::new(&mut code, &options.name_prefix);
+        target_file.public = options.public;
+        writeln!(
+            target_file,
+            "{}mod {} {{",
+            target_file.header(&format!("Information about the target `{}`.", target)),
+            target_mod_name(target)
+        )?;
+        write_cfg(&cfg, &mut target_file)?;
+        if options.endianness_enum {
+            write_target_endianness(&cfg, &mut target_file)?;
+        }
+        writeln!(target_file, "}}")?;
+    }
+    Ok(String::from_utf8(code)?)
+}
+
+/// A shorthand for calling `write_built_file()` with `CARGO_MANIFEST_DIR` and
+/// `[OUT_DIR]/built.rs`.
+///
+/// # Errors
+/// Same as `write_built_file_with_opts()`, or if `CARGO_MANIFEST_DIR` or
+/// `OUT_DIR` are not set.
+pub fn write_built_file() -> Result<(), Error> {
+    let src = env::var_os("CARGO_MANIFEST_DIR").ok_or(Error::MissingEnv("CARGO_MANIFEST_DIR"))?;
+    let dst = path::Path::new(&env::var_os("OUT_DIR").ok_or(Error::MissingEnv("OUT_DIR"))?)
+        .join("built.rs");
+    write_built_file_with_opts(&Options::default(), src.as_ref(), &dst)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CodeWriter, EnvironmentMap};
+
+    fn writer<'a>(buf: &'a mut Vec<u8>, prefix: &'a str) -> CodeWriter<'a> {
+        CodeWriter::new(buf, prefix)
+    }
+
+    /// The environment as given by Cargo to a build-script.
+    fn cargo_envmap() -> EnvironmentMap {
+        [
+            ("CARGO_PKG_VERSION", "1.2.3"),
+            ("CARGO_PKG_VERSION_MAJOR", "1"),
+            ("CARGO_PKG_VERSION_MINOR", "2"),
+            ("CARGO_PKG_VERSION_PATCH", "3"),
+            ("CARGO_PKG_VERSION_PRE", ""),
+            ("CARGO_PKG_AUTHORS", "Joe"),
+            ("CARGO_PKG_NAME", "testbox"),
+            ("CARGO_PKG_DESCRIPTION", ""),
+            ("CARGO_PKG_HOMEPAGE", ""),
+            ("CARGO_PKG_LICENSE", "MIT"),
+            ("CARGO_PKG_REPOSITORY", ""),
+            ("TARGET", "x86_64-unknown-linux-gnu"),
+            ("HOST", "x86_64-unknown-linux-gnu"),
+            ("PROFILE", "debug"),
+            ("RUSTC", "rustc"),
+            ("RUSTDOC", "rustdoc"),
+            ("OPT_LEVEL", "0"),
+            ("NUM_JOBS", "8"),
+            ("DEBUG", "true"),
+        ]
+        .iter()
+        .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+        .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn parse_git_repo() {
+        use super::util;
+        use std::fs;
+        use std::path;
+
+        let repo_root = tempfile::tempdir().unwrap();
+        assert_eq!(util::get_repo_description(repo_root.as_ref()), Ok(None));
+        assert_eq!(util::get_repo_kind(repo_root.as_ref()), Ok(None));
+        assert_eq!(util::get_repo_remotes(repo_root.as_ref()), Ok(None));
+        assert_eq!(util::get_repo_relative_path(repo_root.as_ref()), Ok(None));
+
+        let repo = git2::Repository::init_opts(
+            &repo_root,
+            git2::RepositoryInitOptions::new()
+                .external_template(false)
+                .mkdir(false)
+                .no_reinit(true)
+                .mkpath(false),
+        )
+        .unwrap();
+
+        let cruft_file = repo_root.path().join("cruftfile");
+        std::fs::write(&cruft_file, "Who? Me?").unwrap();
+
+        let project_root = repo_root.path().join("project_root");
+        fs::create_dir(&project_root).unwrap();
+
+        let sig = git2::Signature::now("foo", "bar").unwrap();
+        let mut idx = repo.index().unwrap();
+        idx.add_path(path::Path::new("cruftfile")).unwrap();
+        idx.write().unwrap();
+        let commit_oid = repo
+            .commit(
+                Some("HEAD"),
+                &sig,
+                &sig,
+                "Testing testing 1 2 3",
+                &repo.find_tree(idx.write_tree().unwrap()).unwrap(),
+                &[],
+            )
+            .unwrap();
+
+        let commit_hash = format!("{}", commit_oid);
+
+        assert_eq!(
+            util::get_repo_relative_path(repo_root.as_ref()),
+            Ok(Some("".to_owned()))
+        );
+        let nested_root = project_root.join("nested");
+        fs::create_dir(&nested_root).unwrap();
+        assert_eq!(
+            util::get_repo_relative_path(&nested_root),
+            Ok(Some("project_root/nested".to_owned()))
+        );
+
+        // A plain repository is neither bare nor a worktree
+        assert_eq!(util::get_repo_kind(&project_root), Ok(Some((false, false))));
+
+        assert_eq!(
+            util::get_repo_status(&project_root),
+            Ok(Some("".to_owned()))
+        );
+
+        // The the commit, the commit-id is something and the repo is not dirty
+        let (tag, dirty) = util::get_repo_description(&project_root).unwrap().unwrap();
+        assert!(!tag.is_empty());
+        assert!(!dirty);
+        assert_eq!(util::get_repo_tagged(&project_root), Ok(Some(false)));
+        assert_eq!(util::get_repo_head_tags(&project_root), Ok(Some(vec![])));
+        assert_eq!(util::get_repo_commits_since_tag(&project_root), Ok(None));
+
+        // Tag the commit, it should be retrieved
+        repo.tag(
+            "foobar",
+            &repo
+                .find_object(commit_oid, Some(git2::ObjectType::Commit))
+                .unwrap(),
+            &sig,
+            "Tagged foobar",
+            false,
+        )
+        .unwrap();
+
+        let (tag, dirty) = util::get_repo_description(&project_root).unwrap().unwrap();
+        assert_eq!(tag, "foobar");
+        assert!(!dirty);
+        assert_eq!(util::get_repo_tagged(&project_root), Ok(Some(true)));
+        assert_eq!(
+            util::get_repo_head_tags(&project_root),
+            Ok(Some(vec!["foobar".to_owned()]))
+        );
+        assert_eq!(util::get_repo_commits_since_tag(&project_root), Ok(Some(0)));
+
+        // Untracked files make the repo dirty
+        std::fs::write(repo_root.path().join("new.txt"), "").unwrap();
+        let (_, dirty) = util::get_repo_description(&project_root).unwrap().unwrap();
+        assert!(dirty);
+
+        // Make some dirt
+        std::fs::write(cruft_file, "now dirty").unwrap();
+        assert_eq!(
+            util::get_repo_status(&project_root),
+            Ok(Some(" M cruftfile\n?? new.txt".to_owned()))
+        );
+        let (tag, dirty) = util::get_repo_description(&project_root).unwrap().unwrap();
+        assert_eq!(tag, "foobar");
+        assert!(dirty);
+
+        let branch_short_name = "baz";
+        let branch_name = "refs/heads/baz";
+        let commit = repo.find_commit(commit_oid).unwrap();
+        repo.branch(branch_short_name, &commit, true).unwrap();
+        repo.set_head(branch_name).unwrap();
+
+        assert_eq!(
+            util::get_repo_head(&project_root),
+            Ok(Some((Some(branch_name.to_owned()), commit_hash.clone())))
+        );
+        assert_eq!(util::get_repo_head_detached(&project_root), Ok(Some(false)));
+        let short_hash = util::get_repo_head_short_hash(&project_root)
+            .unwrap()
+            .unwrap();
+        assert!(short_hash.len() >= 7);
+        assert!(commit_hash.starts_with(&short_hash));
+
+        // Without a remote, there is no default branch
+        assert_eq!(util::get_repo_default_branch(&project_root), Ok(None));
+        assert_eq!(util::get_repo_remotes(&project_root), Ok(Some(vec![])));
+
+        repo.remote("upstream", "https://example.com/upstream.git")
+            .unwrap();
+        repo.remote("origin", "git@example.com:fork.git").unwrap();
+        assert_eq!(
+            util::get_repo_remotes(&project_root),
+            Ok(Some(vec![
+                ("origin".to_owned(), "git@example.com:fork.git".to_owned()),
+                (
+                    "upstream".to_owned(),
+                    "https://example.com/upstream.git".to_owned()
+                ),
+            ]))
+        );
+
+        repo.reference("refs/remotes/origin/main", commit_oid, false, "")
+            .unwrap();
+        repo.reference_symbolic(
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/main",
+            false,
+            "",
+        )
+        .unwrap();
+        assert_eq!(
+            util::get_repo_default_branch(&project_root),
+            Ok(Some("main".to_owned()))
+        );
+
+        // Commits after the tag are counted
+        for msg in &["First after tag", "Second after tag"] {
+            let parent = repo.head().unwrap().peel_to_commit().unwrap();
+            repo.commit(
+                Some("HEAD"),
+                &sig,
+                &sig,
+                msg,
+                &parent.tree().unwrap(),
+                &[&parent],
+            )
+            .unwrap();
+        }
+        assert_eq!(util::get_repo_commits_since_tag(&project_root), Ok(Some(2)));
+        assert_eq!(util::get_repo_head_tags(&project_root), Ok(Some(vec![])));
+
+        // The same commit may be tagged multiple times
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.tag_lightweight("v2.0", head.as_object(), false)
+            .unwrap();
+        repo.tag_lightweight("latest", head.as_object(), false)
+            .unwrap();
+        assert_eq!(
+            util::get_repo_head_tags(&project_root),
+            Ok(Some(vec!["latest".to_owned(), "v2.0".to_owned()]))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn release_branch() {
+        let repo_root = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_opts(
+            &repo_root,
+            git2::RepositoryInitOptions::new()
+                .external_template(false)
+                .mkdir(false)
+                .no_reinit(true)
+                .mkpath(false),
+        )
+        .unwrap();
+        let sig = git2::Signature::now("foo", "bar").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let commit_oid = repo
+            .commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])
+            .unwrap();
+        let commit = repo.find_commit(commit_oid).unwrap();
+        repo.branch("release/1.0", &commit, true).unwrap();
+        repo.set_head("refs/heads/release/1.0").unwrap();
+
+        let write = |patterns: &[&str]| {
+            let patterns = patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+            let mut buf = Vec::new();
+            super::write_git_version(
+                repo_root.path(),
+                1024,
+                false,
+                &patterns,
+                &mut writer(&mut buf, ""),
+            )
+            .unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+        assert!(write(&["main", "release/*"])
+            .contains("pub const GIT_ON_RELEASE_BRANCH: Option<bool> = Some(true);"));
+        assert!(write(&["main", "v*"])
+            .contains("pub const GIT_ON_RELEASE_BRANCH: Option<bool> = Some(false);"));
+        assert!(!write(&[]).contains("GIT_ON_RELEASE_BRANCH"));
+
+        repo.set_head_detached(commit_oid).unwrap();
+        let code = write(&["release/*"]);
+        assert!(code.contains("pub const GIT_ON_RELEASE_BRANCH: Option<bool> = None;"));
+        assert!(code.contains("pub const GIT_HEAD_DETACHED: Option<bool> = Some(true);"));
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn glob_match() {
+        use super::glob_match;
+
+        assert!(glob_match("main", "main"));
+        assert!(!glob_match("main", "mainline"));
+        assert!(glob_match("release/*", "release/1.0"));
+        assert!(glob_match("release/*", "release/"));
+        assert!(!glob_match("release/*", "releases/1.0"));
+        assert!(glob_match("v*", "v1.2.3"));
+        assert!(glob_match("*-stable", "1.0-stable"));
+        assert!(glob_match("*/*-rc?", "release/1.0-rc1"));
+        assert!(!glob_match("*/*-rc?", "release/1.0-rc10"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("?", ""));
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn rebuild_triggers() {
+        let repo_root = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init(&repo_root).unwrap();
+        let sig = git2::Signature::now("foo", "bar").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "Initial", &tree, &[])
+            .unwrap();
+        let project_root = repo_root.path().join("project");
+        for dir in &["src", "target", ".cache"] {
+            std::fs::create_dir_all(project_root.join(dir)).unwrap();
+        }
+        std::fs::write(project_root.join("Cargo.toml"), "").unwrap();
+        std::fs::write(repo_root.path().join("Cargo.lock"), "version = 3").unwrap();
+
+        let mut options = super::Options::none();
+        options
+            .set_git(true)
+            .set_dependencies(true)
+            .set_rerun_if_changed(true);
+        let triggers = super::get_rebuild_triggers(&options, &project_root);
+        assert_eq!(triggers[0], "Cargo.toml");
+        assert!(triggers[1].ends_with("Cargo.lock"));
+        assert!(triggers
+            .iter()
+            .any(|trigger| trigger.ends_with(".git/HEAD")));
+        assert!(triggers
+            .iter()
+            .any(|trigger| trigger.ends_with("refs/heads/master")
+                || trigger.ends_with("refs/heads/main")));
+        assert_eq!(triggers.last().unwrap(), "src");
+        assert_eq!(triggers.iter().filter(|&t| t == "Cargo.toml").count(), 1);
+        assert!(!triggers
+            .iter()
+            .any(|trigger| trigger == "target" || trigger == ".cache"));
+
+        let mut buf = Vec::new();
+        super::write_rebuild_triggers(&triggers, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains(&format!(
+            "pub const REBUILD_TRIGGERS: [&str; {}] = {:?};",
+            triggers.len(),
+            triggers
+        )));
+
+        // The package's files are always included, so its other inputs
+        // keep triggering a rerun
+        options.set_git(false);
+        let triggers = super::get_rebuild_triggers(&options, &project_root);
+        assert_eq!(triggers.len(), 3);
+        assert!(triggers[1].ends_with("Cargo.lock"));
+        assert_eq!(triggers[2], "src");
+
+        options.set_rerun_if_changed(false);
+        assert!(super::get_rebuild_triggers(&options, &project_root).is_empty());
+
+        // Without anything to register, Cargo's default is kept
+        options
+            .set_rerun_if_changed(true)
+            .set_dependencies(false)
+            .set_source_hash(true);
+        assert!(super::get_rebuild_triggers(&options, &project_root).is_empty());
+    }
+
+    #[test]
+    fn rerun_env_vars() {
+        let mut options = super::Options::none();
+        assert!(super::get_rerun_env_vars(&options).is_empty());
+
+        // Set by Cargo for the build-script, so never registered
+        options.set_env(true).set_features(true);
+        assert!(super::get_rerun_env_vars(&options).is_empty());
+
+        options.set_ci(true);
+        let vars = super::get_rerun_env_vars(&options);
+        assert!(vars.contains(&"TRAVIS") && vars.contains(&"HEROKU_TEST_RUN_ID"));
+        assert!(vars.contains(&"BUILDER_OUTPUT") && vars.contains(&"CI"));
+
+        options.set_ci(false).set_rust_env(true);
+        assert_eq!(
+            super::get_rerun_env_vars(&options),
+            ["RUST_MIN_STACK", "RUST_BACKTRACE"]
+        );
+
+        #[cfg(feature = "chrono")]
+        {
+            options.set_rust_env(false).set_time(true);
+            assert_eq!(super::get_rerun_env_vars(&options), ["SOURCE_DATE_EPOCH"]);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn cfg_gate() {
+        let repo_root = tempfile::tempdir().unwrap();
+        let mut buf = Vec::new();
+        let mut w = writer(&mut buf, "");
+        super::write_features(&cargo_envmap(), &mut w).unwrap();
+        w.gate = Some("git-info".to_owned());
+        super::write_git_version(repo_root.path(), 1024, false, &[], &mut w).unwrap();
+        w.gate = None;
+        super::write_info_struct(true, false, &mut w).unwrap();
+        super::write_ext_trait(&mut w).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains(
+            "#[cfg(feature = \"git-info\")]\n#[allow(dead_code)]\npub const GIT_VERSION: Option<&str> = None;"
+        ));
+        assert!(code.contains("\n#[allow(dead_code)]\npub const FEATURES: "));
+        assert!(!code
+            .contains("#[cfg(feature = \"git-info\")]\n#[allow(dead_code)]\npub const FEATURES"));
+        assert!(code.contains("    #[cfg(feature = \"git-info\")]\n    pub git_version: "));
+        assert!(code
+            .contains("        #[cfg(feature = \"git-info\")]\n        git_version: GIT_VERSION,"));
+        assert!(code.contains("    #[cfg(feature = \"git-info\")]\n    fn git_version(&self)"));
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn corrupt_git_repo() {
+        let repo_root = tempfile::tempdir().unwrap();
+        git2::Repository::init(&repo_root).unwrap();
+        std::fs::write(repo_root.path().join(".git/HEAD"), "garbage").unwrap();
+        assert!(super::util::get_repo_description(repo_root.as_ref()).is_err());
+
+        let mut buf = Vec::new();
+        super::write_git_version(
+            repo_root.as_ref(),
+            1024,
+            true,
+            &[],
+            &mut writer(&mut buf, ""),
+        )
+        .unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const GIT_VERSION: Option<&str> = None;"));
+        assert!(code.contains("pub const GIT_DIRTY: Option<bool> = None;"));
+        assert!(code.contains("pub const GIT_HEAD_DETACHED: Option<bool> = None;"));
+        assert!(code.contains("pub const GIT_HEAD_TAGS: [&str; 0] = [];"));
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn detached_head_repo() {
+        let repo_root = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_opts(
+            &repo_root,
+            git2::RepositoryInitOptions::new()
+                .external_template(false)
+                .mkdir(false)
+                .no_reinit(true)
+                .mkpath(false),
+        )
+        .unwrap();
+        let sig = git2::Signature::now("foo", "bar").unwrap();
+        let commit_oid = repo
+            .commit(
+                Some("HEAD"),
+                &sig,
+                &sig,
+                "Testing",
+                &repo
+                    .find_tree(repo.index().unwrap().write_tree().unwrap())
+                    .unwrap(),
+                &[],
+            )
+            .unwrap();
+        let commit_hash = format!("{}", commit_oid);
+        repo.set_head_detached(commit_oid).unwrap();
+        assert_eq!(
+            super::util::get_repo_head(repo_root.as_ref()),
+            Ok(Some((None, commit_hash)))
+        );
+        assert_eq!(
+            super::util::get_repo_head_detached(repo_root.as_ref()),
+            Ok(Some(true))
+        );
+    }
+
+    #[test]
+    fn parse_codegen_units() {
+        assert_eq!(super::parse_codegen_units(&[]), None);
+        assert_eq!(
+            super::parse_codegen_units(&["-C", "opt-level=3", "--cfg", "foo"]),
+            None
+        );
+        assert_eq!(
+            super::parse_codegen_units(&["-C", "codegen-units=4"]),
+            Some(4)
+        );
+        assert_eq!(
+            super::parse_codegen_units(&["-Ccodegen-units=1", "--codegen=codegen-units=16"]),
+            Some(16)
+        );
+    }
+
+    #[test]
+    fn codegen_backend() {
+        let mut envmap = cargo_envmap();
+        assert_eq!(super::get_codegen_backend(&envmap), "llvm");
+        envmap.insert(
+            "CARGO_PROFILE_DEV_CODEGEN_BACKEND".to_owned(),
+            "gcc".to_owned(),
+        );
+        assert_eq!(super::get_codegen_backend(&envmap), "gcc");
+        envmap.insert(
+            "RUSTFLAGS".to_owned(),
+            "-C opt-level=1 -Zcodegen-backend=cranelift".to_owned(),
+        );
+        assert_eq!(super::get_codegen_backend(&envmap), "cranelift");
+
+        let mut buf = Vec::new();
+        super::write_env(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const CODEGEN_BACKEND: &str = \"cranelift\";"));
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn source_date_epoch() {
+        let mut envmap = EnvironmentMap::new();
+        envmap.insert("SOURCE_DATE_EPOCH".to_owned(), "1500000000".to_owned());
+        let now = super::get_build_time(&envmap);
+        assert_eq!(now.to_rfc2822(), "Fri, 14 Jul 2017 02:40:00 +0000");
+
+        envmap.insert("SOURCE_DATE_EPOCH".to_owned(), "yesterday".to_owned());
+        let now = super::get_build_time(&envmap);
+        assert!((chrono::offset::Utc::now() - now).num_seconds() < 60);
+    }
+
+    #[test]
+    fn detect_clean_build() {
+        let target_dir = tempfile::tempdir().unwrap();
+        let out_dir = target_dir
+            .path()
+            .join("debug/build/testbox-0123456789abcdef/out");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let mut envmap = cargo_envmap();
+        assert_eq!(super::detect_clean_build(&envmap), None);
+
+        envmap.insert("OUT_DIR".to_owned(), out_dir.to_string_lossy().into_owned());
+        assert_eq!(super::detect_clean_build(&envmap), Some(true));
+
+        let cache_dir = target_dir.path().join("debug/incremental/testbox-abc");
+        std::fs::create_dir_all(cache_dir).unwrap();
+        assert_eq!(super::detect_clean_build(&envmap), Some(false));
+
+        envmap.insert("CARGO_INCREMENTAL".to_owned(), "0".to_owned());
+        assert_eq!(super::detect_clean_build(&envmap), None);
+
+        envmap.insert("CARGO_INCREMENTAL".to_owned(), "1".to_owned());
+        envmap.insert(
+            "OUT_DIR".to_owned(),
+            target_dir.path().to_string_lossy().into_owned(),
+        );
+        assert_eq!(super::detect_clean_build(&envmap), None);
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn time_zone() {
+        use chrono::TimeZone;
+
+        let now = chrono::Utc.timestamp_opt(1_500_000_000, 0).unwrap();
+        let mut buf = Vec::new();
+        let time_zone = chrono::FixedOffset::east_opt(5 * 3600 + 30 * 60);
+        super::write_time(&now, time_zone, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(
+            code.contains("pub const BUILT_TIME_UTC: &str = \"Fri, 14 Jul 2017 02:40:00 +0000\";")
+        );
+        assert!(
+            code.contains("pub const BUILT_TIME_TZ: &str = \"Fri, 14 Jul 2017 08:10:00 +0530\";")
+        );
+
+        let mut options = super::Options::default();
+        options.set_time_zone(86_400);
+        assert_eq!(options.time_zone, None);
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn write_stale_check() {
+        let mut buf = Vec::new();
+        super::write_stale_check(&chrono::offset::Utc::now(), &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub fn warn_if_stale(max_days: i64) -> bool"));
+        assert!(code.contains("BUILT_TIME_UTC"));
+    }
+
+    #[test]
+    fn parse_lib_name() {
+        let manifest = r#"
+            [package]
+            name = "foobar"
+            version = "1.0.0"
+
+            [lib]
+            name = "libfoo"
+            path = "src/lib.rs""#
+            .parse()
+            .unwrap();
+        assert_eq!(super::parse_lib_name(&manifest), Some("libfoo"));
+
+        let manifest = r#"
+            [package]
+            name = "foobar"
+            version = "1.0.0""#
+            .parse()
+            .unwrap();
+        assert_eq!(super::parse_lib_name(&manifest), None);
+    }
+
+    #[test]
+    fn edition() {
+        let root = tempfile::tempdir().unwrap();
+        let member = root.path().join("member");
+        std::fs::create_dir(&member).unwrap();
+        std::fs::write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"foobar\"\nversion = \"1.0.0\"\nedition = \"2021\"",
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        let manifest = super::get_manifest(&member).unwrap();
+        super::write_manifest_info(&member, Some(&manifest), &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const PKG_EDITION: Option<&str> = Some(\"2021\");"));
+        assert!(code.contains("pub const MIN_EDITION_RUSTC: Option<&str> = Some(\"1.56\");"));
+
+        std::fs::write(
+            member.join("Cargo.toml"),
+            "[package]\nname = \"foobar\"\nversion = \"1.0.0\"\nedition.workspace = true",
+        )
+        .unwrap();
+        std::fs::write(
+            root.path().join("Cargo.toml"),
+            "[workspace]\nmembers = [\"member\"]\n\n[workspace.package]\nedition = \"2024\"",
+        )
+        .unwrap();
+        let mut buf = Vec::new();
+        let manifest = super::get_manifest(&member).unwrap();
+        super::write_manifest_info(&member, Some(&manifest), &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const PKG_EDITION: Option<&str> = Some(\"2024\");"));
+        assert!(code.contains("pub const MIN_EDITION_RUSTC: Option<&str> = Some(\"1.85\");"));
+
+        let manifest = "[package]\nname = \"foobar\"".parse().unwrap();
+        assert_eq!(super::parse_edition(&manifest), Some("2015"));
+        assert_eq!(super::min_edition_rustc("2015"), Some("1.0"));
+        assert_eq!(super::min_edition_rustc("2018"), Some("1.31"));
+        assert_eq!(super::min_edition_rustc("2077"), None);
+    }
+
+    #[test]
+    fn name_prefix() {
+        let mut buf = Vec::new();
+        super::write_env(&cargo_envmap(), &mut writer(&mut buf, "BUILT_")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const BUILT_PKG_VERSION: &str = \"1.2.3\";"));
+        assert!(!code.contains("pub const PKG_VERSION:"));
+        assert!(code.contains(
+            "#[doc=r#\"The architecture of the host, parsed from `HOST`.\"#]\n#[allow(dead_code)]\npub const BUILT_HOST_ARCH"
+        ));
+    }
+
+    #[test]
+    fn source_tree_hash() {
+        fn make_tree(main: &str) -> tempfile::TempDir {
+            let root = tempfile::tempdir().unwrap();
+            std::fs::create_dir_all(root.path().join("src/bin")).unwrap();
+            std::fs::write(root.path().join("src/lib.rs"), "pub fn foo() {}").unwrap();
+            std::fs::write(root.path().join("src/bin/main.rs"), main).unwrap();
+            root
+        }
+
+        let a = make_tree("fn main() {}");
+        let b = make_tree("fn main() {}");
+        let c = make_tree("fn main() { foo(); }");
+        let hash_a = super::get_source_tree_hash(a.path()).unwrap();
+        assert_eq!(hash_a, super::get_source_tree_hash(b.path()).unwrap());
+        assert_ne!(hash_a, super::get_source_tree_hash(c.path()).unwrap());
+    }
+
+    #[test]
+    fn postprocess() {
+        let root = tempfile::tempdir().unwrap();
+        let dst = root.path().join("built.rs");
+        let mut options = super::Options::default();
+        options
+            .set_compiler(false)
+            .set_env(false)
+            .set_features(false)
+            .set_postprocess(|code| format!("// Hello, World\n{}", code));
+        super::write_built_file_with_opts(&options, root.path(), &dst).unwrap();
+        let code = std::fs::read_to_string(&dst).unwrap();
+        assert!(code.starts_with("// Hello, World\n"));
+        assert!(code.contains("pub const CI_PLATFORM"));
+    }
+
+    #[test]
+    fn overwrite() {
+        let root = tempfile::tempdir().unwrap();
+        let dst = root.path().join("built.rs");
+        std::fs::write(&dst, "// Stale").unwrap();
+        let mut options = super::Options::default();
+        options
+            .set_compiler(false)
+            .set_env(false)
+            .set_features(false)
+            .set_overwrite(false);
+        match super::write_built_file_with_opts(&options, root.path(), &dst) {
+            Err(super::Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::AlreadyExists),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(std::fs::read_to_string(&dst).unwrap(), "// Stale");
+
+        options.set_overwrite(true);
+        super::write_built_file_with_opts(&options, root.path(), &dst).unwrap();
+        assert!(std::fs::read_to_string(&dst)
+            .unwrap()
+            .contains("pub const CI_PLATFORM"));
+    }
+
+    #[test]
+    fn progress() {
+        let root = tempfile::tempdir().unwrap();
+        let phases = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut options = super::Options::none();
+        let reported = phases.clone();
+        options
+            .set_ci(true)
+            .set_dependencies(true)
+            .set_cfg(true)
+            .set_progress(move |phase| reported.borrow_mut().push(phase.to_owned()));
+        super::write_built_info(&options, root.path(), &mut Vec::new()).unwrap();
+        assert_eq!(*phases.borrow(), ["ci", "dependencies", "cfg"]);
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn signed_commit_repo() {
+        let repo_root = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_opts(
+            &repo_root,
+            git2::RepositoryInitOptions::new()
+                .external_template(false)
+                .mkdir(false)
+                .no_reinit(true)
+                .mkpath(false),
+        )
+        .unwrap();
+        let sig = git2::Signature::now("foo", "bar").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let unsigned_oid = repo
+            .commit(Some("HEAD"), &sig, &sig, "Unsigned", &tree, &[])
+            .unwrap();
+        assert_eq!(
+            super::util::get_repo_commit_signed(repo_root.as_ref()),
+            Ok(Some(false))
+        );
+
+        // The signature is not verified, any content will do
+        let unsigned = repo.find_commit(unsigned_oid).unwrap();
+        let content = repo
+            .commit_create_buffer(&sig, &sig, "Signed", &tree, &[&unsigned])
+            .unwrap();
+        let signed_oid = repo
+            .commit_signed(
+                content.as_str().unwrap(),
+                "-----BEGIN PGP SIGNATURE-----\n\nfoobar\n-----END PGP SIGNATURE-----",
+                None,
+            )
+            .unwrap();
+        repo.set_head_detached(signed_oid).unwrap();
+        assert_eq!(
+            super::util::get_repo_commit_signed(repo_root.as_ref()),
+            Ok(Some(true))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn merge_commit_repo() {
+        let repo_root = tempfile::tempdir().unwrap();
+        assert_eq!(
+            super::util::get_repo_commit_parents(repo_root.as_ref()),
+            Ok(None)
+        );
+        let repo = git2::Repository::init_opts(
+            &repo_root,
+            git2::RepositoryInitOptions::new()
+                .external_template(false)
+                .mkdir(false)
+                .no_reinit(true)
+                .mkpath(false),
+        )
+        .unwrap();
+        let sig = git2::Signature::now("foo", "bar").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let root_oid = repo
+            .commit(Some("HEAD"), &sig, &sig, "Root", &tree, &[])
+            .unwrap();
+        assert_eq!(
+            super::util::get_repo_commit_parents(repo_root.as_ref()),
+            Ok(Some(vec![]))
+        );
+
+        let root = repo.find_commit(root_oid).unwrap();
+        let left_oid = repo
+            .commit(Some("HEAD"), &sig, &sig, "Left", &tree, &[&root])
+            .unwrap();
+        let right_oid = repo
+            .commit(None, &sig, &sig, "Right", &tree, &[&root])
+            .unwrap();
+        let left = repo.find_commit(left_oid).unwrap();
+        let right = repo.find_commit(right_oid).unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "Merge", &tree, &[&left, &right])
+            .unwrap();
+        assert_eq!(
+            super::util::get_repo_commit_parents(repo_root.as_ref()),
+            Ok(Some(vec![left_oid.to_string(), right_oid.to_string()]))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn first_parent_description() {
+        let repo_root = tempfile::tempdir().unwrap();
+        let repo = git2::Repository::init_opts(
+            &repo_root,
+            git2::RepositoryInitOptions::new()
+                .external_template(false)
+                .mkdir(false)
+                .no_reinit(true)
+                .mkpath(false),
+        )
+        .unwrap();
+        let sig = git2::Signature::now("foo", "bar").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let root_oid = repo
+            .commit(Some("HEAD"), &sig, &sig, "Root", &tree, &[])
+            .unwrap();
+        let root = repo.find_commit(root_oid).unwrap();
+        repo.tag_lightweight("v1", root.as_object(), false).unwrap();
+        assert_eq!(
+            super::util::get_repo_description_first_parent(repo_root.as_ref()),
+            Ok(Some("v1".to_owned()))
+        );
+
+        // The tag on the merged-in branch is closer to HEAD
+        let main_oid = repo
+            .commit(Some("HEAD"), &sig, &sig, "Main", &tree, &[&root])
+            .unwrap();
+        let side_oid = repo
+            .commit(None, &sig, &sig, "Side", &tree, &[&root])
+            .unwrap();
+        let main = repo.find_commit(main_oid).unwrap();
+        let side = repo.find_commit(side_oid).unwrap();
+        repo.tag_lightweight("side-v2", side.as_object(), false)
+            .unwrap();
+        let merge_oid = repo
+            .commit(Some("HEAD"), &sig, &sig, "Merge", &tree, &[&main, &side])
+            .unwrap();
+        let short_id = repo
+            .find_object(merge_oid, None)
+            .unwrap()
+            .short_id()
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .to_owned();
+
+        let (tag, _) = super::util::get_repo_description(repo_root.as_ref())
+            .unwrap()
+            .unwrap();
+        assert!(tag.starts_with("side-v2-"));
+        assert_eq!(
+            super::util::get_repo_description_first_parent(repo_root.as_ref()),
+            Ok(Some(format!("v1-2-g{}", short_id)))
+        );
+    }
+
+    #[test]
+    fn parse_deps_by_registry() {
+        let lock_toml_buf = r#"
+            version = 3
+
+            [[package]]
+            name = "foobar"
+            version = "1.0.0"
+            dependencies = [
+                "normal_dep",
+                "private_dep",
+                "sparse_dep",
+            ]
+
+            [[package]]
+            name = "normal_dep"
+            version = "1.2.3"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "private_dep"
+            version = "4.5.6"
+            source = "registry+https://example.com/private-index"
+
+            [[package]]
+            name = "sparse_dep"
+            version = "7.8.9"
+            source = "sparse+https://index.crates.io/""#;
+        let registries = super::parse_dependencies_by_registry(&lock_toml_buf.parse().unwrap());
+        assert_eq!(
+            registries,
+            [
+                (
+                    "https://example.com/private-index".to_owned(),
+                    vec!["private_dep".to_owned()]
+                ),
+                (
+                    "https://github.com/rust-lang/crates.io-index".to_owned(),
+                    vec!["normal_dep".to_owned()]
+                ),
+                (
+                    "https://index.crates.io/".to_owned(),
+                    vec!["sparse_dep".to_owned()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn build_std() {
+        let mut buf = Vec::new();
+        super::write_build_std(&EnvironmentMap::new(), &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const BUILD_STD: bool = false;"));
+        assert!(code.contains("pub const BUILD_STD_FEATURES: [&str; 0] = [];"));
+
+        let mut envmap = EnvironmentMap::new();
+        envmap.insert(
+            "CARGO_UNSTABLE_BUILD_STD".to_owned(),
+            "core, alloc".to_owned(),
+        );
+        envmap.insert(
+            "CARGO_UNSTABLE_BUILD_STD_FEATURES".to_owned(),
+            "panic_immediate_abort,".to_owned(),
+        );
+        let mut buf = Vec::new();
+        super::write_build_std(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const BUILD_STD: bool = true;"));
+        assert!(
+            code.contains("pub const BUILD_STD_FEATURES: [&str; 1] = [\"panic_immediate_abort\"];")
+        );
+    }
+
+    #[test]
+    fn const_fn() {
+        let mut buf = Vec::new();
+        let mut w = writer(&mut buf, "BUILT_");
+        super::write_build_std(&EnvironmentMap::new(), &mut w).unwrap();
+        super::write_info_struct(true, false, &mut w).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub struct BuiltBuiltInfo {"));
+        assert!(code.contains("    pub build_std_features: [&'static str; 0],\n"));
+        assert!(code.contains("pub const fn built_built_info() -> BuiltBuiltInfo {"));
+        assert!(code.contains("        build_std: BUILT_BUILD_STD,\n"));
+        assert!(!code.contains("BUILT_INFO"));
+    }
+
+    #[test]
+    fn info_struct() {
+        let mut buf = Vec::new();
+        let mut w = writer(&mut buf, "");
+        super::write_build_std(&EnvironmentMap::new(), &mut w).unwrap();
+        super::write_info_struct(false, true, &mut w).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert_eq!(code.matches("pub struct BuiltInfo {").count(), 1);
+        assert!(code.contains(
+            "pub const INFO: BuiltInfo = BuiltInfo {\n    \
+             build_std: BUILD_STD,\n    \
+             build_std_features: BUILD_STD_FEATURES,\n};"
+        ));
+        assert!(!code.contains("const fn"));
+
+        let mut buf = Vec::new();
+        let mut w = writer(&mut buf, "");
+        super::write_build_std(&EnvironmentMap::new(), &mut w).unwrap();
+        super::write_info_struct(true, true, &mut w).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert_eq!(code.matches("pub struct BuiltInfo {").count(), 1);
+        assert!(code.contains("pub const fn built_info() -> BuiltInfo {"));
+        assert!(code.contains("pub const INFO: BuiltInfo = BuiltInfo {"));
+    }
+
+    #[test]
+    fn ext_trait() {
+        let mut buf = Vec::new();
+        let mut w = writer(&mut buf, "BUILT_");
+        super::write_env(&cargo_envmap(), &mut w).unwrap();
+        super::write_ext_trait(&mut w).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub trait BuiltBuiltInfoExt {"));
+        assert!(code.contains("    fn pkg_version(&self) -> &'static str;\n"));
+        assert!(code.contains("pub struct BuiltBuildInfo;"));
+        assert!(code.contains("impl BuiltBuiltInfoExt for BuiltBuildInfo {"));
+        assert!(code.contains(
+            "    fn pkg_version(&self) -> &'static str {\n        BUILT_PKG_VERSION\n    }\n"
+        ));
+    }
+
+    #[test]
+    fn private_items() {
+        let mut buf = Vec::new();
+        let mut w = writer(&mut buf, "");
+        w.public = false;
+        super::write_env(&cargo_envmap(), &mut w).unwrap();
+        super::write_info_struct(true, false, &mut w).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("\nconst PKG_VERSION: &str = \"1.2.3\";"));
+        assert!(code.contains("\nconst fn built_info() -> BuiltInfo {"));
+        assert!(!code.contains("pub const"));
+        assert!(!code.contains("#[doc"));
+    }
+
+    #[test]
+    fn registry_auth() {
+        let cargo_home = tempfile::tempdir().unwrap();
+        let mut envmap = EnvironmentMap::new();
+        envmap.insert(
+            "CARGO_HOME".to_owned(),
+            cargo_home.path().to_string_lossy().into_owned(),
+        );
+        assert!(!super::detect_registry_auth(&envmap));
+
+        std::fs::write(
+            cargo_home.path().join("credentials.toml"),
+            "[registries.my-registry]\ntoken = \"hunter2\"",
+        )
+        .unwrap();
+        assert!(super::detect_registry_auth(&envmap));
+        std::fs::remove_file(cargo_home.path().join("credentials.toml")).unwrap();
+
+        envmap.insert(
+            "CARGO_REGISTRIES_MY_REGISTRY_TOKEN".to_owned(),
+            "hunter2".to_owned(),
+        );
+        let mut buf = Vec::new();
+        super::write_registry_auth(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const PRIVATE_REGISTRY_AUTH_PRESENT: bool = true;"));
+        assert!(!code.contains("hunter2"));
+    }
+
+    #[test]
+    fn parse_non_default_features() {
+        let manifest = r#"
+            [package]
+            name = "foobar"
+            version = "1.0.0"
+
+            [features]
+            default = ["foo", "serde/std"]
+            foo = ["bar-baz", "dep:serde"]
+            bar-baz = []
+            extra = []"#
+            .parse()
+            .unwrap();
+        let features = ["BAR_BAZ", "DEFAULT", "EXTRA", "FOO"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            super::parse_non_default_features(&features, &manifest),
+            ["EXTRA"]
+        );
+    }
+
+    #[test]
+    fn parse_feature_names() {
+        let manifest = r#"
+            [package]
+            name = "foobar"
+            version = "1.0.0"
+
+            [dependencies]
+            serde_json = { version = "1.0", optional = true }
+            toml = "0.8"
+
+            [features]
+            default = ["bar-baz"]
+            bar-baz = []"#
+            .parse()
+            .unwrap();
+        let features = ["BAR_BAZ", "DEFAULT", "SERDE_JSON", "UNDECLARED"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            super::parse_feature_names(&features, &manifest),
+            ["bar-baz", "default", "serde_json", "undeclared"]
+        );
+
+        let features = ["BAR_BAZ", "DEFAULT"];
+        assert!(super::util::has_feature(&features, "bar-baz"));
+        assert!(super::util::has_feature(&features, "BAR_BAZ"));
+        assert!(!super::util::has_feature(&features, "serde_json"));
+    }
+
+    fn detect_ci(vars: &[(&str, &str)]) -> Option<super::CIPlatform> {
+        let envmap = vars
+            .iter()
+            .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+            .collect();
+        super::CIPlatform::detect_from_envmap(&envmap)
+    }
+
+    #[test]
+    fn detect_azure_pipelines() {
+        use super::CIPlatform;
+
+        assert_eq!(detect_ci(&[("TF_BUILD", "True")]), Some(CIPlatform::TFS));
+        assert_eq!(
+            detect_ci(&[("TF_BUILD", "True"), ("AZURE_HTTP_USER_AGENT", "VSTS")]),
+            Some(CIPlatform::AzurePipelines)
+        );
+        assert_eq!(
+            detect_ci(&[
+                ("TF_BUILD", "True"),
+                (
+                    "SYSTEM_TEAMFOUNDATIONSERVERURI",
+                    "https://dev.azure.com/foobar/"
+                )
+            ]),
+            Some(CIPlatform::AzurePipelines)
+        );
+        assert_eq!(CIPlatform::AzurePipelines.to_string(), "Azure Pipelines");
+    }
+
+    #[test]
+    fn detect_codebuild() {
+        assert_eq!(
+            detect_ci(&[(
+                "CODEBUILD_BUILD_ID",
+                "codebuild-demo-project:b1e6661e-e4f2-4156-9ab9-82a19EXAMPLE"
+            )]),
+            Some(super::CIPlatform::CodeBuild)
+        );
+    }
+
+    #[test]
+    fn rust_env() {
+        let mut envmap = EnvironmentMap::new();
+        envmap.insert("RUST_BACKTRACE".to_owned(), "1".to_owned());
+        envmap.insert("RUST_LOG".to_owned(), "debug".to_owned());
+        let mut buf = Vec::new();
+        super::write_rust_env(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains(
+            "pub const BUILD_RUST_ENV: [(&str, &str); 1] = [(\"RUST_BACKTRACE\", \"1\")];"
+        ));
+    }
+
+    #[test]
+    fn detect_cirrus() {
+        assert_eq!(
+            detect_ci(&[("CI", "true"), ("CIRRUS_CI", "true")]),
+            Some(super::CIPlatform::Cirrus)
+        );
+    }
+
+    #[test]
+    fn detect_google_cloud_build() {
+        assert_eq!(
+            detect_ci(&[("BUILDER_OUTPUT", "/builder/outputs")]),
+            Some(super::CIPlatform::GoogleCloudBuild)
+        );
+        assert_eq!(
+            detect_ci(&[
+                ("PROJECT_ID", "foobar"),
+                ("BUILD_ID", "f0f1f2f3-0000-4000-8000-000000000000")
+            ]),
+            None
+        );
+        assert_eq!(detect_ci(&[("BUILD_ID", "42")]), None);
+        assert_eq!(
+            super::CIPlatform::GoogleCloudBuild.to_string(),
+            "Google Cloud Build"
+        );
+    }
+
+    #[test]
+    fn detect_bitrise() {
+        assert_eq!(
+            detect_ci(&[
+                ("CI", "true"),
+                ("BITRISE_IO", "true"),
+                ("BITRISE_BUILD_NUMBER", "42")
+            ]),
+            Some(super::CIPlatform::Bitrise)
+        );
+        assert_eq!(super::CIPlatform::Bitrise.to_string(), "Bitrise");
+    }
 
-    macro_rules! o {
-        ($i:ident, $b:stmt) => {
-            if options.$i {
-                $b
+    #[test]
+    fn detect_heroku() {
+        assert_eq!(
+            detect_ci(&[("CI", "true"), ("HEROKU_TEST_RUN_ID", "abc-123")]),
+            Some(super::CIPlatform::Heroku)
+        );
+        assert_eq!(super::CIPlatform::Heroku.to_string(), "Heroku CI");
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn truncate_str() {
+        let mut s = "M über.rs".to_owned();
+        super::truncate_str(&mut s, 3);
+        assert_eq!(s, "M ");
+        super::truncate_str(&mut s, 3);
+        assert_eq!(s, "M ");
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn release_conditions() {
+        use super::ReleaseConditions;
+
+        let release = ReleaseConditions::default();
+        let dev = ReleaseConditions {
+            git_clean: false,
+            git_tagged: false,
+            not_path_dependency: false,
+            ci: false,
+        };
+        let local_checkout = ReleaseConditions {
+            ci: false,
+            ..release
+        };
+        let untagged = ReleaseConditions {
+            git_tagged: false,
+            ..release
+        };
+
+        assert!(release.is_met_by(&release));
+        assert!(!release.is_met_by(&dev));
+        assert!(!release.is_met_by(&local_checkout));
+        assert!(!release.is_met_by(&untagged));
+
+        // Ignoring the CI-condition allows local release-builds
+        let no_ci = ReleaseConditions {
+            ci: false,
+            ..release
+        };
+        assert!(no_ci.is_met_by(&local_checkout));
+        assert!(!no_ci.is_met_by(&untagged));
+
+        // Nothing required, everything goes
+        assert!(dev.is_met_by(&dev));
+    }
+
+    #[test]
+    #[cfg(feature = "git2")]
+    fn is_path_dependency() {
+        let cargo_home = tempfile::tempdir().unwrap();
+        let mut envmap = EnvironmentMap::new();
+        envmap.insert(
+            "CARGO_HOME".to_owned(),
+            cargo_home.path().to_str().unwrap().to_owned(),
+        );
+
+        let registry_crate = cargo_home.path().join("registry/src/index/foo-0.1.0");
+        assert!(!super::is_path_dependency(&envmap, &registry_crate));
+
+        let toplevel = tempfile::tempdir().unwrap();
+        std::fs::write(toplevel.path().join("Cargo.lock"), "").unwrap();
+        assert!(!super::is_path_dependency(&envmap, toplevel.path()));
+
+        let member = toplevel.path().join("crates/member");
+        std::fs::create_dir_all(&member).unwrap();
+        assert!(!super::is_path_dependency(&envmap, &member));
+
+        let path_dep = tempfile::tempdir().unwrap();
+        assert!(super::is_path_dependency(&envmap, path_dep.path()));
+    }
+
+    #[test]
+    fn parse_lockfile_version() {
+        let lock_toml_buf = r#"
+            version = 3
+
+            [[package]]
+            name = "foobar"
+            version = "1.0.0""#;
+        assert_eq!(
+            super::parse_lockfile_version(&lock_toml_buf.parse().unwrap()),
+            3
+        );
+
+        let lock_toml_buf = r#"
+            [[package]]
+            name = "foobar"
+            version = "1.0.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            checksum = "76b5842e81eb9bbea19276a9dbbda22ac042532f390a67ab08b895617978abf3""#;
+        assert_eq!(
+            super::parse_lockfile_version(&lock_toml_buf.parse().unwrap()),
+            2
+        );
+
+        let lock_toml_buf = r#"
+            [[package]]
+            name = "foobar"
+            version = "1.0.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [metadata]
+            "checksum foobar 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)" = "76b5842e81eb9bbea19276a9dbbda22ac042532f390a67ab08b895617978abf3""#;
+        assert_eq!(
+            super::parse_lockfile_version(&lock_toml_buf.parse().unwrap()),
+            1
+        );
+    }
+
+    #[test]
+    fn malformed_lockfile() {
+        for lock_buf in &[
+            // Truncated tables
+            "[[package]",
+            "[[package]]\nname = \"foo",
+            "version = 3\n[[package]]\nname = \"foo\"\nversion = \"1.0",
+            // Wrong types
+            "version = \"three\"",
+            "version = -1\n[[package]]\nname = \"foo\"\nversion = \"1.0.0\"",
+            "package = 1",
+            "[[package]]\nname = 1\nversion = \"1.0.0\"",
+            "[[package]]\nname = \"foo\"\nversion = \"1.0.0\"\ndependencies = \"bar\"",
+            // Missing fields
+            "[[package]]\nname = \"foo\"",
+            "[[package]]\nversion = \"1.0.0\"",
+            "version = 3\n[[package]]\nname = \"foo\"\nversion = \"1.0.0\"\ndependencies = [\"bar\"]",
+            // Invalid values
+            "[[package]]\nname = \"foo\"\nversion = \"not a version\"",
+            "[[package]]\nname = \"foo\"\nversion = \"1.0.0\"\nsource = \"nonsense\"",
+            "[[package]]\nname = \"foo\"\nversion = \"1.0.0\"\nchecksum = \"xyz\"",
+        ] {
+            let manifest_dir = tempfile::tempdir().unwrap();
+            std::fs::write(manifest_dir.path().join("Cargo.lock"), lock_buf).unwrap();
+            if let Ok(lockfile) = super::get_lockfile(manifest_dir.path()) {
+                super::parse_dependencies(&lockfile);
+                super::parse_dependencies_by_registry(&lockfile);
             }
+        }
+    }
+
+    #[test]
+    fn features_count() {
+        let mut envmap = cargo_envmap();
+        envmap.insert("CARGO_FEATURE_DEFAULT".to_owned(), "1".to_owned());
+        envmap.insert("CARGO_FEATURE_WAYLAND".to_owned(), "1".to_owned());
+        let mut buf = Vec::new();
+        super::write_features(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const FEATURES: [&str; 2] = [\"DEFAULT\", \"WAYLAND\"];"));
+        assert!(code.contains("pub const FEATURES_COUNT: usize = 2;"));
+    }
+
+    #[test]
+    fn features_hash() {
+        let hash = super::get_features_hash(&["DEFAULT", "WAYLAND"]);
+        assert_eq!(hash.len(), 16);
+        assert_eq!(hash, super::get_features_hash(&["WAYLAND", "DEFAULT"]));
+        assert_ne!(hash, super::get_features_hash(&["DEFAULT"]));
+        assert_ne!(hash, super::get_features_hash(&["DEFAULT", "X11"]));
+        // The separator keeps concatenations apart
+        assert_ne!(
+            super::get_features_hash(&["AB", "C"]),
+            super::get_features_hash(&["A", "BC"])
+        );
+    }
+
+    #[test]
+    fn package_metadata() {
+        let metadata = super::PackageMetadata {
+            name: "foobar".to_owned(),
+            version: "1.2.3-rc1+build5".to_owned(),
+            authors: vec!["Joe".to_owned(), "Bob".to_owned()],
+            description: "A description".to_owned(),
+            homepage: "localhost".to_owned(),
+            license: "MIT".to_owned(),
+            repository: "https://example.com/foobar".to_owned(),
         };
+        let mut envmap = EnvironmentMap::new();
+        metadata.apply(&mut envmap);
+
+        let mut buf = Vec::new();
+        super::write_package(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        for expected in &[
+            r#"pub const PKG_NAME: &str = "foobar";"#,
+            r#"pub const PKG_VERSION: &str = "1.2.3-rc1+build5";"#,
+            r#"pub const PKG_VERSION_MAJOR: &str = "1";"#,
+            r#"pub const PKG_VERSION_MINOR: &str = "2";"#,
+            r#"pub const PKG_VERSION_PATCH: &str = "3";"#,
+            r#"pub const PKG_VERSION_PRE: &str = "rc1";"#,
+            r#"pub const PKG_AUTHORS: &str = "Joe:Bob";"#,
+            r#"pub const PKG_DESCRIPTION: &str = "A description";"#,
+            r#"pub const PKG_HOMEPAGE: &str = "localhost";"#,
+            r#"pub const PKG_LICENSE: &str = "MIT";"#,
+            r#"pub const PKG_REPOSITORY: &str = "https://example.com/foobar";"#,
+            r#"pub const PKG_LICENSE_FILE: &str = "";"#,
+            r#"pub const PKG_RUST_VERSION: &str = "";"#,
+            "pub const PKG_VERSION_MAJOR_NUM: u64 = 1;",
+            "pub const PKG_VERSION_MINOR_NUM: u64 = 2;",
+            "pub const PKG_VERSION_PATCH_NUM: u64 = 3;",
+        ] {
+            assert!(code.contains(expected), "{} not in {}", expected, code);
+        }
+
+        // A missing license is not an error
+        let mut envmap = cargo_envmap();
+        envmap.remove("CARGO_PKG_LICENSE");
+        envmap.remove("CARGO_PKG_REPOSITORY");
+        let mut buf = Vec::new();
+        super::write_package(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains(r#"pub const PKG_LICENSE: &str = "";"#));
+        assert!(code.contains(r#"pub const PKG_REPOSITORY: &str = "";"#));
+
+        // Metadata overrides the environment
+        let mut envmap = cargo_envmap();
+        metadata.apply(&mut envmap);
+        assert_eq!(envmap["CARGO_PKG_NAME"], "foobar");
+        assert_eq!(envmap["TARGET"], "x86_64-unknown-linux-gnu");
     }
-    if options.ci || options.env || options.features || options.compiler {
-        let envmap = get_environment();
-        o!(ci, write_ci(&envmap, &mut built_file)?);
-        o!(env, write_env(&envmap, &mut built_file)?);
-        o!(features, write_features(&envmap, &mut built_file)?);
-        o!(
-            compiler,
-            write_compiler_version(
-                envmap["RUSTC"].as_ref(),
-                envmap["RUSTDOC"].as_ref(),
-                &mut built_file
-            )?
+
+    #[test]
+    fn missing_package_env() {
+        let mut envmap = cargo_envmap();
+        envmap.remove("CARGO_PKG_HOMEPAGE");
+        let mut buf = Vec::new();
+        super::write_env(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains(r#"pub const PKG_HOMEPAGE: &str = "";"#));
+        assert!(code.contains(r#"pub const PKG_NAME: &str = "testbox";"#));
+
+        envmap.insert("CARGO_PKG_RUST_VERSION".to_owned(), "1.56".to_owned());
+        let mut buf = Vec::new();
+        super::write_env(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains(r#"pub const PKG_RUST_VERSION: &str = "1.56";"#));
+
+        envmap.insert("CARGO_PKG_VERSION_MINOR".to_owned(), "two".to_owned());
+        match super::write_env(&envmap, &mut writer(&mut Vec::new(), "")) {
+            Err(super::Error::InvalidEnv("CARGO_PKG_VERSION_MINOR", value)) => {
+                assert_eq!(value, "two")
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn generated_assets() {
+        let mut options = super::Options::default();
+        options
+            .register_generated_asset("logo", "assets/logo.png")
+            .register_generated_asset("schema", "schema.json");
+        let mut buf = Vec::new();
+        super::write_generated_assets(&options.generated_assets, &mut writer(&mut buf, ""))
+            .unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains(
+            r#"pub const GENERATED_ASSETS: [(&str, &str); 2] = [("logo", "assets/logo.png"), ("schema", "schema.json")];"#
+        ));
+        assert!(code.contains("pub fn generated_asset(name: &str) -> Option<&'static str> {"));
+        assert!(code.contains(
+            r#"        "schema" => Some(concat!(env!("OUT_DIR"), "/", "schema.json")),"#
+        ));
+    }
+
+    #[test]
+    fn endianness() {
+        let mut buf = Vec::new();
+        super::write_endianness("big", &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub enum Endianness {\n"));
+        assert!(code.contains("    Little,\n    #[doc=r#\"Big endian.\"#]\n    Big,\n}"));
+        assert!(code.contains("pub const ENDIANNESS: Endianness = Endianness::Big;"));
+
+        let mut buf = Vec::new();
+        super::write_endianness("little", &mut writer(&mut buf, "BUILT_")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub enum BuiltEndianness {\n"));
+        assert!(
+            code.contains("pub const BUILT_ENDIANNESS: BuiltEndianness = BuiltEndianness::Little;")
+        );
+
+        let mut buf = Vec::new();
+        super::write_endianness("", &mut writer(&mut buf, "")).unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn escape_str() {
+        assert_eq!(super::escape_str("foobar"), r#""foobar""#);
+        assert_eq!(
+            super::escape_str("A \"quoted\"\ndescription\\"),
+            r#""A \"quoted\"\ndescription\\""#
+        );
+        assert_eq!(super::escape_str("Grüße"), r#""Grüße""#);
+
+        let mut envmap = cargo_envmap();
+        envmap.insert(
+            "CARGO_PKG_DESCRIPTION".to_owned(),
+            "A \"quoted\"\ndescription".to_owned(),
+        );
+        let mut buf = Vec::new();
+        super::write_package(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains(r#"pub const PKG_DESCRIPTION: &str = "A \"quoted\"\ndescription";"#));
+    }
+
+    #[test]
+    fn parse_built_json() {
+        use super::util::{parse_built_json, BuildInfoValue};
+
+        let info = parse_built_json(
+            r#"{
+                "PKG_VERSION": "1.2.3",
+                "PKG_DESCRIPTION": "A \"quoted\"\ndescription \u00fc \ud83d\ude00",
+                "NUM_JOBS": 8,
+                "DEBUG": true,
+                "CI_PLATFORM": null,
+                "FEATURES": ["DEFAULT", "WAYLAND"],
+                "DEPENDENCIES": [["built", "0.5.1"]],
+                "EMPTY": {}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            info["PKG_VERSION"],
+            BuildInfoValue::String("1.2.3".to_owned())
+        );
+        assert_eq!(
+            info["PKG_DESCRIPTION"],
+            BuildInfoValue::String("A \"quoted\"\ndescription ü 😀".to_owned())
+        );
+        assert_eq!(info["NUM_JOBS"], BuildInfoValue::Number(8.0));
+        assert_eq!(info["DEBUG"], BuildInfoValue::Bool(true));
+        assert_eq!(info["CI_PLATFORM"], BuildInfoValue::Null);
+        assert_eq!(
+            info["FEATURES"],
+            BuildInfoValue::Array(vec![
+                BuildInfoValue::String("DEFAULT".to_owned()),
+                BuildInfoValue::String("WAYLAND".to_owned()),
+            ])
+        );
+        assert_eq!(
+            info["DEPENDENCIES"],
+            BuildInfoValue::Array(vec![BuildInfoValue::Array(vec![
+                BuildInfoValue::String("built".to_owned()),
+                BuildInfoValue::String("0.5.1".to_owned()),
+            ])])
+        );
+        assert_eq!(info["EMPTY"], BuildInfoValue::Object(Default::default()));
+
+        for (malformed, offset) in &[
+            ("", 0),
+            ("[]", 0),
+            (r#"{"PKG_VERSION": "1.2.3""#, 23),
+            (r#"{"PKG_VERSION" "1.2.3"}"#, 15),
+            (r#"{"PKG_VERSION": "1.2.3}"#, 23),
+            (r#"{"DEBUG": maybe}"#, 10),
+            (r#"{"FEATURES": ["DEFAULT",]}"#, 24),
+            (r#"{} {}"#, 3),
+        ] {
+            let err = parse_built_json(malformed).unwrap_err();
+            assert_eq!(err.offset, *offset, "{:?}: {}", malformed, err);
+        }
+        assert_eq!(
+            parse_built_json("[]").unwrap_err().to_string(),
+            "Expected an object at offset 0"
+        );
+
+        let nested = |depth: usize| format!("{{\"a\":{}{}}}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse_built_json(&nested(127)).is_ok());
+        let err = parse_built_json(&nested(200_000)).unwrap_err();
+        assert_eq!(err.message, "Nesting too deep");
+        assert_eq!(err.offset, 5 + 127);
+    }
+
+    #[test]
+    fn parse_rustc_channel() {
+        let verbose_version = |release: &str| {
+            format!(
+                "rustc {}\nbinary: rustc\nhost: x86_64-unknown-linux-gnu\nrelease: {}\nLLVM version: 18.1.7\n",
+                release, release
+            )
+        };
+        assert_eq!(
+            super::parse_rustc_channel(&verbose_version("1.80.0")),
+            Some("stable")
+        );
+        assert_eq!(
+            super::parse_rustc_channel(&verbose_version("1.81.0-beta.3")),
+            Some("beta")
+        );
+        assert_eq!(
+            super::parse_rustc_channel(&verbose_version("1.82.0-nightly")),
+            Some("nightly")
+        );
+        assert_eq!(
+            super::parse_rustc_channel(&verbose_version("1.82.0-dev")),
+            None
+        );
+        assert_eq!(super::parse_rustc_channel("rustc 1.80.0"), None);
+    }
+
+    #[test]
+    fn parse_verbose_version() {
+        let verbose_version = "rustc 1.80.0 (051478957 2024-07-21)
+binary: rustc
+commit-hash: 051478957371ee0084a7c0913941d2a8c4757bb9
+commit-date: 2024-07-21
+host: x86_64-unknown-linux-gnu
+release: 1.80.0
+LLVM version: 18.1.7
+";
+        assert_eq!(
+            super::parse_verbose_version(verbose_version, "commit-hash"),
+            Some("051478957371ee0084a7c0913941d2a8c4757bb9")
+        );
+        assert_eq!(
+            super::parse_verbose_version(verbose_version, "commit-date"),
+            Some("2024-07-21")
+        );
+        assert_eq!(
+            super::parse_verbose_version(verbose_version, "host"),
+            Some("x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(
+            super::parse_verbose_version(verbose_version, "LLVM version"),
+            Some("18.1.7")
+        );
+        assert_eq!(
+            super::parse_verbose_version(verbose_version, "foobar"),
+            None
+        );
+        assert_eq!(
+            super::parse_verbose_version("commit-hash: unknown", "commit-hash"),
+            None
+        );
+    }
+
+    #[test]
+    fn for_profile() {
+        let debug = super::Options::for_profile("debug");
+        assert!(!debug.compiler);
+        assert!(!debug.deps);
+        assert!(!debug.time);
+        assert!(debug.env);
+
+        let release = super::Options::for_profile("release");
+        assert!(release.compiler);
+        assert!(release.deps);
+        assert!(release.time);
+
+        let custom = super::Options::for_profile("custom");
+        assert!(custom.compiler);
+        assert!(!custom.deps);
+    }
+
+    #[test]
+    fn set_all() {
+        let mut options = super::Options::default();
+        options.set_all(false);
+        assert!(!options.compiler && !options.git && !options.ci && !options.env);
+        assert!(!options.deps && !options.features && !options.time);
+        assert!(options.cfg);
+
+        options.set_all(true);
+        assert!(options.compiler && options.ci && options.env);
+        assert!(options.deps && options.features);
+        assert_eq!(options.git, cfg!(feature = "git2"));
+        assert_eq!(options.time, cfg!(feature = "chrono"));
+    }
+
+    #[test]
+    fn build_host() {
+        let mut buf = Vec::new();
+        super::write_build_host(&mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains(&format!(
+            "pub const BUILD_OS: &str = \"{}\";",
+            std::env::consts::OS
+        )));
+        assert!(code.contains(&format!(
+            "pub const BUILD_ARCH: &str = \"{}\";",
+            std::env::consts::ARCH
+        )));
+        if cfg!(unix) {
+            assert!(super::get_build_kernel().is_some());
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn check_environment() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let invalid = std::ffi::OsString::from_vec(b"foo\xffbar".to_vec());
+        let vars = vec![
+            ("TARGET".into(), "x86_64-unknown-linux-gnu".into()),
+            ("CARGO_PKG_DESCRIPTION".into(), invalid.clone()),
+        ];
+        assert!(super::check_environment(vars.clone()).is_ok());
+
+        let vars = vars.into_iter().chain(Some(("RUSTC".into(), invalid)));
+        match super::check_environment(vars) {
+            Err(super::Error::InvalidEnv("RUSTC", value)) => assert_eq!(value, "foo\u{fffd}bar"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "semver")]
+    fn pkg_version_fn() {
+        let mut envmap = cargo_envmap();
+        let mut buf = Vec::new();
+        super::write_pkg_version_fn(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub fn pkg_version() -> built::semver::Version {"));
+        assert!(code.contains("major: 1,\n        minor: 2,\n        patch: 3,"));
+        assert!(code.contains("pre: built::semver::Prerelease::EMPTY,"));
+        assert!(code.contains("build: built::semver::BuildMetadata::EMPTY,"));
+        assert!(!code.contains("const"));
+
+        envmap.insert("CARGO_PKG_VERSION".to_owned(), "1.2.3-rc1+b5".to_owned());
+        let mut buf = Vec::new();
+        super::write_pkg_version_fn(&envmap, &mut writer(&mut buf, "BUILT_")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub fn built_pkg_version() -> "));
+        assert!(code.contains(
+            "pre: built::semver::Prerelease::new(\"rc1\")\
+             .expect(\"The pre-release was validated at build-time\"),"
+        ));
+        assert!(code.contains(
+            "build: built::semver::BuildMetadata::new(\"b5\")\
+             .expect(\"The build-metadata was validated at build-time\"),"
+        ));
+        assert!(!code.contains("unwrap()"));
+
+        envmap.insert("CARGO_PKG_VERSION".to_owned(), "one".to_owned());
+        assert!(matches!(
+            super::write_pkg_version_fn(&envmap, &mut writer(&mut Vec::new(), "")),
+            Err(super::Error::InvalidEnv("CARGO_PKG_VERSION", _))
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let mut buf = Vec::new();
+        let err = super::write_env(&EnvironmentMap::new(), &mut writer(&mut buf, "")).unwrap_err();
+        assert!(matches!(err, super::Error::MissingEnv("TARGET")));
+        assert_eq!(
+            err.to_string(),
+            "Missing expected environment variable TARGET"
+        );
+
+        let manifest_dir = tempfile::tempdir().unwrap();
+        std::fs::write(manifest_dir.path().join("Cargo.toml"), "[package").unwrap();
+        let err = super::get_manifest(manifest_dir.path()).unwrap_err();
+        assert!(matches!(err, super::Error::Toml(_)));
+        assert!(std::error::Error::source(&err).is_some());
+
+        let err = super::get_lockfile(manifest_dir.path()).unwrap_err();
+        assert!(matches!(err, super::Error::Io(_)));
+
+        #[cfg(feature = "serde")]
+        {
+            let err = super::Error::from(serde_json::from_str::<u32>("[").unwrap_err());
+            assert!(err.to_string().starts_with("Failed to convert to JSON: "));
+            assert!(std::error::Error::source(&err).is_some());
+        }
+    }
+
+    #[test]
+    fn literal_to_json() {
+        use super::literal_to_json;
+
+        assert_eq!(literal_to_json("true").as_deref(), Some("true"));
+        assert_eq!(literal_to_json("8").as_deref(), Some("8"));
+        assert_eq!(literal_to_json("None").as_deref(), Some("null"));
+        assert_eq!(
+            literal_to_json(r#"Some("a \"b\"\n\u{1b}")"#).as_deref(),
+            Some(r#""a \"b\"\n\u001b""#)
+        );
+        assert_eq!(
+            literal_to_json(r#"[("foo", "1.0"), ("bar", "2.0")]"#).as_deref(),
+            Some(r#"[["foo","1.0"],["bar","2.0"]]"#)
+        );
+        assert_eq!(
+            literal_to_json(r#"[("https://foo", &["bar"])]"#).as_deref(),
+            Some(r#"[["https://foo",["bar"]]]"#)
+        );
+        assert_eq!(literal_to_json("[]").as_deref(), Some("[]"));
+        assert_eq!(
+            literal_to_json("Endianness::Little").as_deref(),
+            Some(r#""Little""#)
+        );
+        assert_eq!(literal_to_json("Some(8"), None);
+        assert_eq!(literal_to_json("[1 2]"), None);
+    }
+
+    #[test]
+    fn write_json() {
+        let mut envmap = cargo_envmap();
+        envmap.insert(
+            "CARGO_PKG_DESCRIPTION".to_owned(),
+            "A \"quoted\"\tdescription\\".to_owned(),
+        );
+        let mut buf = Vec::new();
+        let mut w = writer(&mut buf, "BUILT_");
+        super::write_package(&envmap, &mut w).unwrap();
+        super::write_features(&cargo_envmap(), &mut w).unwrap();
+        let json = super::items_to_json(&w.items);
+        super::write_json(&mut w).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains(&format!(
+            "pub const BUILT_BUILT_JSON: &str = {};",
+            super::escape_str(&json)
+        )));
+
+        let info = super::util::parse_built_json(&json).unwrap();
+        assert_eq!(
+            info["PKG_VERSION"],
+            super::util::BuildInfoValue::String("1.2.3".to_owned())
+        );
+        assert_eq!(info["FEATURES"], super::util::BuildInfoValue::Array(vec![]));
+        assert_eq!(
+            info["PKG_DESCRIPTION"],
+            super::util::BuildInfoValue::String("A \"quoted\"\tdescription\\".to_owned())
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn gather_as_json_value() {
+        let mut options = super::Options::default();
+        options
+            .set_compiler(false)
+            .set_env(false)
+            .set_dependencies(false);
+        options.git = false;
+        options.time = false;
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        let info = super::gather_as_json_value(&options, manifest_dir.as_ref()).unwrap();
+        for key in &["CI_PLATFORM", "FEATURES", "FEATURES_STR", "CFG_OS"] {
+            assert!(info.get(key).is_some(), "{} missing in {}", key, info);
+        }
+        assert!(info["FEATURES"].is_array());
+        assert!(info["CFG_OS"].is_string());
+        assert!(info.get("PKG_VERSION").is_none());
+    }
+
+    #[test]
+    fn yanked_dependencies() {
+        assert_eq!(super::get_index_path("a"), "1/a");
+        assert_eq!(super::get_index_path("ab"), "2/ab");
+        assert_eq!(super::get_index_path("abc"), "3/a/abc");
+        assert_eq!(super::get_index_path("Serde"), "se/rd/serde");
+
+        let lock_toml_buf = r#"
+            version = 3
+
+            [[package]]
+            name = "foobar"
+            version = "1.0.0"
+            dependencies = [
+                "serde",
+                "log",
+            ]
+
+            [[package]]
+            name = "serde"
+            version = "1.0.1"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "log"
+            version = "0.4.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+        "#;
+        let lockfile = lock_toml_buf.parse().unwrap();
+
+        let cargo_home = tempfile::tempdir().unwrap();
+        let mut envmap = EnvironmentMap::new();
+        envmap.insert(
+            "CARGO_HOME".to_owned(),
+            cargo_home.path().to_str().unwrap().to_owned(),
+        );
+        assert!(super::get_yanked_dependencies(&envmap, &lockfile).is_empty());
+
+        let index_dir = cargo_home
+            .path()
+            .join("registry/index/index.crates.io-6f17d22bba15001f/.cache/se/rd");
+        std::fs::create_dir_all(&index_dir).unwrap();
+        std::fs::write(
+            index_dir.join("serde"),
+            "\u{3}\u{3}\0\0\0abcdef\0\
+             1.0.0\0{\"name\":\"serde\",\"vers\":\"1.0.0\",\"deps\":[],\"yanked\":false}\0\
+             1.0.1\0{\"name\":\"serde\",\"vers\":\"1.0.1\",\"deps\":[],\"yanked\":true}\0",
+        )
+        .unwrap();
+        assert_eq!(
+            super::get_yanked_dependencies(&envmap, &lockfile),
+            ["serde 1.0.1"]
+        );
+
+        // A yank in another registry does not count
+        let index_dir = cargo_home
+            .path()
+            .join("registry/index/example.com-0123456789abcdef/.cache/3/l");
+        std::fs::create_dir_all(&index_dir).unwrap();
+        std::fs::write(
+            index_dir.join("log"),
+            "{\"name\":\"log\",\"vers\":\"0.4.0\",\"deps\":[],\"yanked\":true}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            super::get_yanked_dependencies(&envmap, &lockfile),
+            ["serde 1.0.1"]
+        );
+    }
+
+    #[test]
+    fn parse_triple() {
+        assert_eq!(
+            super::parse_triple("x86_64-unknown-linux-gnu"),
+            ("x86_64", "unknown", "linux", "gnu")
+        );
+        assert_eq!(
+            super::parse_triple("aarch64-apple-darwin"),
+            ("aarch64", "apple", "darwin", "")
+        );
+        assert_eq!(
+            super::parse_triple("x86_64-pc-windows-msvc"),
+            ("x86_64", "pc", "windows", "msvc")
+        );
+        assert_eq!(
+            super::parse_triple("aarch64-linux-android"),
+            ("aarch64", "unknown", "linux", "android")
+        );
+        assert_eq!(
+            super::parse_triple("wasm32-wasi"),
+            ("wasm32", "unknown", "wasi", "")
+        );
+
+        let mut buf = Vec::new();
+        super::write_env(&cargo_envmap(), &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const HOST_ARCH: &str = \"x86_64\";"));
+        assert!(code.contains("pub const HOST_ENV: &str = \"gnu\";"));
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn diff_built() {
+        use chrono::TimeZone;
+
+        fn gather(now: &chrono::DateTime<chrono::offset::Utc>) -> (String, super::GatheredInfo) {
+            let mut buf = Vec::new();
+            let mut w = writer(&mut buf, "BUILT_");
+            super::write_env(&cargo_envmap(), &mut w).unwrap();
+            super::write_time(now, None, &mut w).unwrap();
+            let items = std::mem::take(&mut w.items);
+            let info = super::GatheredInfo {
+                prefix: "BUILT_".to_owned(),
+                public: true,
+                items,
+            };
+            (String::from_utf8(buf).unwrap(), info)
+        }
+
+        let (old_src, old) = gather(&chrono::Utc.timestamp_opt(1_000_000_000, 0).unwrap());
+        assert_eq!(super::diff_built(&old_src, old), "");
+
+        let (_, new) = gather(&chrono::Utc.timestamp_opt(1_500_000_000, 0).unwrap());
+        assert_eq!(
+            super::diff_built(&old_src, new),
+            "#[doc=r#\"The build time in RFC2822, UTC.\"#]\n#[allow(dead_code)]\npub const BUILT_BUILT_TIME_UTC: &str = \"Fri, 14 Jul 2017 02:40:00 +0000\";\n\
+             #[doc=r#\"The build time in seconds since the Unix epoch.\"#]\n#[allow(dead_code)]\npub const BUILT_BUILT_TIME_UNIX: i64 = 1500000000;\n"
         );
+    }
+
+    #[test]
+    fn write_built_info() {
+        let mut options = super::Options::default();
+        options
+            .set_ci(false)
+            .set_env(false)
+            .set_compiler(false)
+            .set_dependencies(false)
+            .set_postprocess(|code| code.replace("FEATURES", "FEATURES_RENAMED"));
         #[cfg(feature = "git2")]
-        {
-            o!(git, write_git_version(&manifest_location, &mut built_file)?);
-        }
+        options.set_git(false);
+        let mut code = Vec::new();
+        super::write_built_info(&options, env!("CARGO_MANIFEST_DIR").as_ref(), &mut code).unwrap();
+        let code = String::from_utf8(code).unwrap();
+        assert!(code.starts_with("//\n// EVERYTHING BELOW THIS POINT WAS AUTO-GENERATED"));
+        assert!(code.contains("pub const FEATURES_RENAMED: "));
+        assert!(code.contains("pub const CFG_OS: "));
     }
-    o!(
-        deps,
-        write_dependencies(manifest_location, &mut built_file)?
-    );
-    #[cfg(feature = "chrono")]
-    {
-        o!(time, write_time(&mut built_file)?);
+
+    #[test]
+    fn write_built_info_from_metadata() {
+        let mut options = super::Options::default();
+        options.set_package_metadata(super::PackageMetadata {
+            name: "foobar".to_owned(),
+            version: "1.2.3".to_owned(),
+            ..Default::default()
+        });
+        // Neither a `Cargo.toml` nor any of Cargo's variables
+        let manifest_dir = tempfile::tempdir().unwrap();
+        let mut code = Vec::new();
+        super::write_built_info_from(
+            &options,
+            manifest_dir.path(),
+            &EnvironmentMap::new(),
+            &[],
+            &mut code,
+        )
+        .unwrap();
+        let code = String::from_utf8(code).unwrap();
+        for expected in &[
+            r#"pub const PKG_NAME: &str = "foobar";"#,
+            r#"pub const PKG_VERSION: &str = "1.2.3";"#,
+            r#"pub const TARGET: &str = "";"#,
+            r#"pub const RUSTC: &str = "rustc";"#,
+            "pub const NUM_JOBS: u32 = 0;",
+            "pub const DEBUG: bool = false;",
+            "pub const LIB_NAME: Option<&str> = None;",
+            "pub const PKG_EDITION: Option<&str> = None;",
+            "pub const MIN_EDITION_RUSTC: Option<&str> = None;",
+            "pub const NON_DEFAULT_FEATURES: [&str; 0] = [];",
+            "pub const FEATURE_NAMES: [&str; 0] = [];",
+        ] {
+            assert!(code.contains(expected), "{} not in {}", expected, code);
+        }
     }
-    o!(cfg, write_cfg(&mut built_file)?);
-    built_file.write_all(
-        r#"//
-// EVERYTHING ABOVE THIS POINT WAS AUTO-GENERATED DURING COMPILATION. DO NOT MODIFY.
-//
-"#
-        .as_ref(),
-    )?;
-    Ok(())
-}
 
-/// A shorthand for calling `write_built_file()` with `CARGO_MANIFEST_DIR` and
-/// `[OUT_DIR]/built.rs`.
-///
-/// # Errors
-/// Same as `write_built_file_with_opts()`.
-///
-/// # Panics
-/// If `CARGO_MANIFEST_DIR` or `OUT_DIR` are not set.
-pub fn write_built_file() -> io::Result<()> {
-    let src = env::var("CARGO_MANIFEST_DIR").unwrap();
-    let dst = path::Path::new(&env::var("OUT_DIR").unwrap()).join("built.rs");
-    write_built_file_with_opts(&Options::default(), src.as_ref(), &dst)?;
-    Ok(())
-}
+    #[test]
+    fn parse_target_cfg() {
+        let cfg = super::parse_target_cfg(
+            "debug_assertions\ntarget_arch=\"aarch64\"\ntarget_env=\"\"\n\
+             target_family=\"unix\"\ntarget_has_atomic=\"32\"\ntarget_has_atomic=\"64\"\nunix\n",
+        );
+        assert_eq!(cfg["CARGO_CFG_TARGET_ARCH"], "aarch64");
+        assert_eq!(cfg["CARGO_CFG_TARGET_ENV"], "");
+        assert_eq!(cfg["CARGO_CFG_TARGET_HAS_ATOMIC"], "32,64");
+        assert_eq!(cfg["CARGO_CFG_UNIX"], "");
+        assert_eq!(super::target_mod_name("wasm32-wasi"), "wasm32_wasi");
+    }
 
-#[cfg(test)]
-mod tests {
+    #[test]
+    fn gather_for_targets() {
+        let mut options = super::Options::default();
+        options
+            .set_ci(false)
+            .set_env(false)
+            .set_features(false)
+            .set_compiler(false)
+            .set_dependencies(false);
+        #[cfg(feature = "git2")]
+        options.set_git(false);
+        let code = super::gather_for_targets(
+            &["x86_64-unknown-linux-gnu", "aarch64-apple-darwin"],
+            &options,
+            env!("CARGO_MANIFEST_DIR").as_ref(),
+        )
+        .unwrap();
+        assert!(code.contains(
+            "pub const TARGETS: [&str; 2] = [\"x86_64-unknown-linux-gnu\", \"aarch64-apple-darwin\"];"
+        ));
+        let linux = code.find("pub mod x86_64_unknown_linux_gnu {").unwrap();
+        let darwin = code.find("pub mod aarch64_apple_darwin {").unwrap();
+        assert!(code[linux..darwin].contains("pub const CFG_OS: &str = \"linux\";"));
+        assert!(code[darwin..].contains("pub const CFG_OS: &str = \"macos\";"));
+        assert!(code[darwin..].contains("pub const CFG_TARGET_VENDOR: &str = \"apple\";"));
+    }
 
     #[test]
-    #[cfg(feature = "git2")]
-    fn parse_git_repo() {
-        use super::util;
-        use std::fs;
-        use std::path;
+    fn missing_lockfile() {
+        let manifest_dir = tempfile::tempdir().unwrap();
+        let mut buf = Vec::new();
+        super::write_dependencies(
+            &EnvironmentMap::new(),
+            manifest_dir.path(),
+            true,
+            &mut writer(&mut buf, ""),
+        )
+        .unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const CARGO_LOCK_VERSION: u32 = 0;"));
+        assert!(code.contains("pub const DEPENDENCIES: [(&str, &str); 0] = [];"));
+        assert!(code.contains("pub const DEPENDENCIES_STR: &str = \"\";"));
+        assert!(code.contains("pub const DEPENDENCIES_COUNT: usize = 0;"));
+        assert!(code.contains("pub const YANKED_DEPENDENCIES: [&str; 0] = [];"));
 
-        let repo_root = tempfile::tempdir().unwrap();
-        assert_eq!(util::get_repo_description(repo_root.as_ref()), Ok(None));
+        // Other errors are not ignored
+        std::fs::create_dir(manifest_dir.path().join("Cargo.lock")).unwrap();
+        let err = super::write_dependencies(
+            &EnvironmentMap::new(),
+            manifest_dir.path(),
+            true,
+            &mut writer(&mut Vec::new(), ""),
+        )
+        .unwrap_err();
+        assert!(matches!(err, super::Error::Io(_)));
+    }
 
-        let repo = git2::Repository::init_opts(
-            &repo_root,
-            git2::RepositoryInitOptions::new()
-                .external_template(false)
-                .mkdir(false)
-                .no_reinit(true)
-                .mkpath(false),
+    #[test]
+    fn workspace_lockfile() {
+        let workspace_root = tempfile::tempdir().unwrap();
+        let member = workspace_root.path().join("crates/member");
+        std::fs::create_dir_all(&member).unwrap();
+        assert_eq!(super::find_lockfile(&member), None);
+
+        std::fs::write(
+            workspace_root.path().join("Cargo.lock"),
+            "version = 3\n\n[[package]]\nname = \"member\"\nversion = \"0.1.0\"\n",
         )
         .unwrap();
+        assert_eq!(
+            super::find_lockfile(&member),
+            Some(workspace_root.path().join("Cargo.lock"))
+        );
+        let lockfile = super::get_lockfile(&member).unwrap();
+        assert_eq!(super::parse_lockfile_version(&lockfile), 3);
+        assert_eq!(
+            super::parse_dependencies(&lockfile),
+            [("member".to_owned(), "0.1.0".to_owned())]
+        );
+    }
 
-        let cruft_file = repo_root.path().join("cruftfile");
-        std::fs::write(&cruft_file, "Who? Me?").unwrap();
+    #[test]
+    fn ci_build_url() {
+        use super::util::ci_build_url;
 
-        let project_root = repo_root.path().join("project_root");
-        fs::create_dir(&project_root).unwrap();
+        let github = [
+            ("GITHUB_REPOSITORY", "lukaslueg/built"),
+            ("GITHUB_RUN_ID", "1234567890"),
+        ];
+        assert_eq!(
+            ci_build_url("GitHub Actions", &github).as_deref(),
+            Some("https://github.com/lukaslueg/built/actions/runs/1234567890")
+        );
+        assert_eq!(ci_build_url("GitHub Actions", &github[..1]), None);
 
-        let sig = git2::Signature::now("foo", "bar").unwrap();
-        let mut idx = repo.index().unwrap();
-        idx.add_path(path::Path::new("cruftfile")).unwrap();
-        idx.write().unwrap();
-        let commit_oid = repo
-            .commit(
-                Some("HEAD"),
-                &sig,
-                &sig,
-                "Testing testing 1 2 3",
-                &repo.find_tree(idx.write_tree().unwrap()).unwrap(),
-                &[],
-            )
-            .unwrap();
+        let gitlab = [
+            (
+                "CI_PIPELINE_URL",
+                "https://gitlab.com/foo/bar/-/pipelines/17",
+            ),
+            ("CI_JOB_URL", "https://gitlab.com/foo/bar/-/jobs/42"),
+        ];
+        assert_eq!(
+            ci_build_url("GitLab", &gitlab).as_deref(),
+            Some("https://gitlab.com/foo/bar/-/jobs/42")
+        );
+        assert_eq!(
+            ci_build_url("GitLab", &gitlab[..1]).as_deref(),
+            Some("https://gitlab.com/foo/bar/-/pipelines/17")
+        );
+        assert_eq!(ci_build_url("Generic CI", &gitlab), None);
+    }
 
-        let commit_hash = format!("{}", commit_oid);
+    #[test]
+    fn parse_dependency_sources() {
+        let lock_toml_buf = r#"
+            version = 3
 
-        // The the commit, the commit-id is something and the repo is not dirty
-        let (tag, dirty) = util::get_repo_description(&project_root).unwrap().unwrap();
-        assert!(!tag.is_empty());
-        assert!(!dirty);
+            [[package]]
+            name = "foo"
+            version = "0.1.0"
+            source = "git+https://github.com/foo/foo#0123456789abcdef0123456789abcdef01234567"
 
-        // Tag the commit, it should be retrieved
-        repo.tag(
-            "foobar",
-            &repo
-                .find_object(commit_oid, Some(git2::ObjectType::Commit))
-                .unwrap(),
-            &sig,
-            "Tagged foobar",
-            false,
+            [[package]]
+            name = "bar"
+            version = "0.2.0"
+            source = "registry+https://example.com/index"
+
+            [[package]]
+            name = "baz"
+            version = "0.3.0""#;
+        let lockfile = lock_toml_buf.parse().unwrap();
+        let deps = super::parse_dependencies(&lockfile);
+        let sources = super::parse_dependency_sources(&lockfile);
+        assert_eq!(
+            deps.iter()
+                .map(|(name, _)| name.as_str())
+                .zip(sources)
+                .collect::<Vec<_>>(),
+            [("bar", "registry"), ("baz", "path"), ("foo", "git")]
+        );
+    }
+
+    #[test]
+    fn options_all_none() {
+        let all = super::Options::all();
+        assert!(all.compiler && all.deps && all.yanked_deps && all.cfg && all.json && all.const_fn);
+        assert!(all.ci && all.env && all.features && all.source_hash);
+        assert!(all.info_struct && all.ext_trait && all.endianness_enum);
+        assert!(all.registry_auth && all.rust_env && all.build_host && all.gather_duration);
+        assert_eq!(all.git, cfg!(feature = "git2"));
+        assert_eq!(all.git_first_parent, cfg!(feature = "git2"));
+        assert_eq!(all.likely_release_build, cfg!(feature = "git2"));
+        assert_eq!(all.hg, cfg!(feature = "hg"));
+        assert_eq!(all.time, cfg!(feature = "chrono"));
+        assert_eq!(all.stale_check, cfg!(feature = "chrono"));
+        assert!(!all.pkg_version_fn);
+        assert!(all.public && all.rerun_if_changed && all.overwrite);
+
+        let none = super::Options::none();
+        assert!(!none.compiler && !none.git && !none.env && !none.deps && !none.time);
+        assert!(!none.cfg && !none.ci && !none.features && !none.json);
+        assert!(none.public);
+    }
+
+    #[test]
+    fn gather_duration() {
+        let mut buf = Vec::new();
+        super::write_gather_duration(
+            std::time::Duration::from_micros(1_500),
+            &mut writer(&mut buf, ""),
         )
         .unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const BUILT_GATHER_DURATION_MS: u64 = 1;"));
+    }
 
-        let (tag, dirty) = util::get_repo_description(&project_root).unwrap().unwrap();
-        assert_eq!(tag, "foobar");
-        assert!(!dirty);
+    #[test]
+    fn wasm_flavor() {
+        let mut envmap = cargo_envmap();
+        assert_eq!(super::parse_wasm_flavor(&envmap), None);
 
-        // Make some dirt
-        std::fs::write(cruft_file, "now dirty").unwrap();
-        let (tag, dirty) = util::get_repo_description(&project_root).unwrap().unwrap();
-        assert_eq!(tag, "foobar");
-        assert!(dirty);
+        envmap.insert("CARGO_CFG_TARGET_ARCH".to_owned(), "wasm32".to_owned());
+        envmap.insert("CARGO_CFG_TARGET_OS".to_owned(), "wasi".to_owned());
+        let mut buf = Vec::new();
+        super::write_env(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const IS_WASM: bool = true;"));
+        assert!(code.contains("pub const WASM_FLAVOR: Option<&str> = Some(\"wasi\");"));
 
-        let branch_short_name = "baz";
-        let branch_name = "refs/heads/baz";
-        let commit = repo.find_commit(commit_oid).unwrap();
-        repo.branch(branch_short_name, &commit, true).unwrap();
-        repo.set_head(branch_name).unwrap();
+        envmap.insert("CARGO_CFG_TARGET_OS".to_owned(), "unknown".to_owned());
+        assert_eq!(super::parse_wasm_flavor(&envmap), Some("unknown"));
+        envmap.insert("CARGO_CFG_TARGET_OS".to_owned(), "emscripten".to_owned());
+        assert_eq!(super::parse_wasm_flavor(&envmap), Some("emscripten"));
+    }
+
+    #[test]
+    fn parse_rfc822() {
+        use super::util::{parse_rfc822, Rfc822Time};
 
         assert_eq!(
-            util::get_repo_head(&project_root),
-            Ok(Some((Some(branch_name.to_owned()), commit_hash)))
+            parse_rfc822("Fri, 14 Jul 2017 02:40:00 +0000"),
+            Some(Rfc822Time {
+                year: 2017,
+                month: 7,
+                day: 14,
+                hour: 2,
+                minute: 40,
+                second: 0,
+                offset: 0,
+            })
+        );
+        let t = parse_rfc822("29 Feb 2024 23:15 -0130").unwrap();
+        assert_eq!((t.year, t.month, t.day, t.second), (2024, 2, 29, 0));
+        assert_eq!(t.offset, -5_400);
+        assert_eq!(t.unix_timestamp(), 1_709_253_900);
+        assert_eq!(
+            parse_rfc822("Thu, 01 Jan 1970 00:00:00 GMT").map(|t| t.unix_timestamp()),
+            Some(0)
         );
+        assert_eq!(
+            parse_rfc822("Wed, 31 Dec 1969 23:59:59 UTC").map(|t| t.unix_timestamp()),
+            Some(-1)
+        );
+
+        for invalid in &[
+            "",
+            "Fri, 14 Foo 2017 02:40:00 +0000",
+            "Fri, 32 Jul 2017 02:40:00 +0000",
+            "Fri, 14 Jul 2017 24:40:00 +0000",
+            "Fri, 14 Jul 2017 02:40:00",
+            "Fri, 14 Jul 2017 02:40:00 CEST",
+            "Fri, 14 Jul 2017 02:40:00:00 +0000",
+            "Fri, 14 Jul 2017 02:40:00 +0000 trailing",
+            "Fri, 14 Jul 2017 02:40:00 +1é2",
+            "Fri, 14 Jul 2017 02:40:00 +9999",
+            "Fri, 14 Jul 2017 02:40:00 +2400",
+            "Fri, 14 Jul 2017 02:40:00 +0060",
+            "Fri, 14 Jul 2017 02:40:00 +-100",
+        ] {
+            assert_eq!(parse_rfc822(invalid), None, "{}", invalid);
+        }
     }
 
     #[test]
-    #[cfg(feature = "git2")]
-    fn detached_head_repo() {
-        let repo_root = tempfile::tempdir().unwrap();
-        let repo = git2::Repository::init_opts(
-            &repo_root,
-            git2::RepositoryInitOptions::new()
-                .external_template(false)
-                .mkdir(false)
-                .no_reinit(true)
-                .mkpath(false),
-        )
-        .unwrap();
-        let sig = git2::Signature::now("foo", "bar").unwrap();
-        let commit_oid = repo
-            .commit(
-                Some("HEAD"),
-                &sig,
-                &sig,
-                "Testing",
-                &repo
-                    .find_tree(repo.index().unwrap().write_tree().unwrap())
-                    .unwrap(),
-                &[],
-            )
-            .unwrap();
-        let commit_hash = format!("{}", commit_oid);
-        repo.set_head_detached(commit_oid).unwrap();
+    fn write_cfg() {
+        let cfg = [
+            ("CARGO_CFG_TARGET_ARCH", "powerpc"),
+            ("CARGO_CFG_TARGET_ENDIAN", "big"),
+            ("CARGO_CFG_TARGET_POINTER_WIDTH", "32"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let mut buf = Vec::new();
+        super::write_cfg(&cfg, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const CFG_ENDIAN: &str = \"big\";"));
+        assert!(code.contains("pub const CFG_POINTER_WIDTH: u8 = 32;"));
+
+        let mut buf = Vec::new();
+        super::write_cfg(&super::EnvironmentMap::new(), &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const CFG_POINTER_WIDTH: u8 = 0;"));
+    }
+
+    #[test]
+    fn profile_name() {
+        let mut envmap = cargo_envmap();
+        envmap.insert(
+            "OUT_DIR".to_owned(),
+            "/work/target/production/build/foo-0123456789abcdef/out".to_owned(),
+        );
+        envmap.insert("CARGO_CFG_DEBUG_ASSERTIONS".to_owned(), "".to_owned());
+        let mut buf = Vec::new();
+        super::write_env(&envmap, &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const PROFILE_NAME: Option<&str> = Some(\"production\");"));
+        assert!(code.contains("pub const DEBUG_ASSERTIONS: bool = true;"));
+
+        envmap.insert(
+            "OUT_DIR".to_owned(),
+            "/work/target/x86_64-unknown-linux-gnu/debug/build/foo-0123456789abcdef/out".to_owned(),
+        );
+        assert_eq!(super::detect_profile_name(&envmap).as_deref(), Some("dev"));
+        envmap.insert("OUT_DIR".to_owned(), "/somewhere/else".to_owned());
+        assert_eq!(super::detect_profile_name(&envmap), None);
+    }
+
+    #[test]
+    fn compat_shim() {
+        let old_src = r##"
+            #[doc=r#"If the crate was compiled from within a git-repository."#]
+            pub const GIT_HASH: Option<&str> = Some("18b2eabfb47998c296f9d5183f617f1b1cc2d321");
+            pub const PKG_VERSION: &str = "1.2.3";
+            pub const CFG_POINTER_WIDTH: &str = "64";
+            pub const GIT_DIRTY: Option<bool> = None;
+            pub const GIT_TAG: Option<&str> = None;
+        "##;
+        let new_src = r#"
+            pub const GIT_COMMIT_HASH: Option<&str> = Some("18b2eabfb47998c296f9d5183f617f1b1cc2d321");
+            pub const PKG_VERSION: &str = "1.2.3";
+            pub const CFG_POINTER_WIDTH: u8 = 64;
+            pub const GIT_DIRTY: Option<bool> = None;
+            pub const GIT_VERSION: Option<&str> = None;
+            pub const GIT_DESCRIBE: Option<&str> = None;
+        "#;
+        let migration = super::compat::compare(old_src, new_src);
         assert_eq!(
-            super::util::get_repo_head(repo_root.as_ref()),
-            Ok(Some((None, commit_hash)))
+            migration.renamed,
+            [(
+                "GIT_HASH".to_owned(),
+                "GIT_COMMIT_HASH".to_owned(),
+                "Option<&str>".to_owned()
+            )]
+        );
+        // Ambiguous, both new constants are `None`
+        assert_eq!(migration.removed, ["GIT_TAG"]);
+        assert_eq!(migration.added, ["GIT_DESCRIBE", "GIT_VERSION"]);
+        assert_eq!(migration.retyped, ["CFG_POINTER_WIDTH"]);
+        assert_eq!(
+            migration.shim(),
+            "#[doc=r#\"Renamed to `GIT_COMMIT_HASH`.\"#]\n\
+             #[deprecated(note = \"renamed to `GIT_COMMIT_HASH`\")]\n\
+             #[allow(dead_code)]\n\
+             pub const GIT_HASH: Option<&str> = GIT_COMMIT_HASH;\n"
+        );
+        assert_eq!(super::compat::compare(new_src, new_src).shim(), "");
+    }
+
+    #[cfg(feature = "hg")]
+    #[test]
+    fn hg_version() {
+        assert_eq!(
+            super::util::parse_hg_id("ca2af4f1b2c3 tip\n"),
+            Some("ca2af4f1b2c3".to_owned())
+        );
+        assert_eq!(
+            super::util::parse_hg_id("ca2af4f1b2c3+ tip v1.0\n"),
+            Some("v1.0".to_owned())
         );
+        assert_eq!(super::util::parse_hg_id(""), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(super::util::get_hg_description(dir.path()).unwrap(), None);
+        let mut buf = Vec::new();
+        super::write_hg_version(dir.path(), &mut writer(&mut buf, "")).unwrap();
+        let code = String::from_utf8(buf).unwrap();
+        assert!(code.contains("pub const HG_VERSION: Option<&str> = None;"));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn time_ago() {
+        use super::util::fmt_age;
+        assert_eq!(fmt_age(-1), "in the future");
+        assert_eq!(fmt_age(0), "just now");
+        assert_eq!(fmt_age(59), "just now");
+        assert_eq!(fmt_age(60), "1 minute ago");
+        assert_eq!(fmt_age(7_199), "1 hour ago");
+        assert_eq!(fmt_age(7_200), "2 hours ago");
+        assert_eq!(fmt_age(3 * 86_400 + 5), "3 days ago");
+        assert_eq!(fmt_age(40 * 86_400), "1 month ago");
+        assert_eq!(fmt_age(800 * 86_400), "2 years ago");
+
+        let past = chrono::offset::Utc::now() - chrono::Duration::hours(5);
+        assert_eq!(super::util::time_ago(&past.to_rfc2822()), "5 hours ago");
+        let future = chrono::offset::Utc::now() + chrono::Duration::hours(5);
+        assert_eq!(super::util::time_ago(&future.to_rfc2822()), "in the future");
     }
 
     #[test]
@@ -1142,7 +7498,7 @@ mod tests {
             [[package]]
             name = "dep_of_dep"
             version = "7.8.9""#;
-        let deps = super::parse_dependencies(lock_toml_buf);
+        let deps = super::parse_dependencies(&lock_toml_buf.parse().unwrap());
         assert_eq!(
             deps,
             [
@@ -1152,4 +7508,117 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn parse_dependency_resolution() {
+        let manifest = r#"
+            [package]
+            name = "foobar"
+            version = "1.0.0"
+
+            [dependencies]
+            normal_dep = "^1.2"
+            renamed = { package = "semver", version = "1.0" }
+            local = { path = "../local" }
+            unresolved = "0.1"
+
+            [build-dependencies]
+            normal_dep = "^1.2"
+        "#;
+        let lockfile = r#"
+            version = 3
+
+            [[package]]
+            name = "foobar"
+            version = "1.0.0"
+            dependencies = [
+             "local",
+             "normal_dep",
+             "semver",
+            ]
+
+            [[package]]
+            name = "local"
+            version = "0.2.0"
+
+            [[package]]
+            name = "normal_dep"
+            version = "1.4.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "semver"
+            version = "1.0.7"
+            source = "registry+https://github.com/rust-lang/crates.io-index""#;
+        let resolution = super::parse_dependency_resolution(
+            &manifest.parse().unwrap(),
+            &lockfile.parse().unwrap(),
+        );
+        assert_eq!(
+            resolution,
+            [
+                ("local".to_owned(), "*".to_owned(), "0.2.0".to_owned()),
+                (
+                    "normal_dep".to_owned(),
+                    "^1.2".to_owned(),
+                    "1.4.0".to_owned()
+                ),
+                ("semver".to_owned(), "1.0".to_owned(), "1.0.7".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_deps_v3() {
+        let lock_toml_buf = r#"
+            # This file is automatically @generated by Cargo.
+            # It is not intended for manual editing.
+            version = 3
+
+            [[package]]
+            name = "foobar"
+            version = "1.0.0"
+            dependencies = [
+             "normal_dep",
+             "semver 1.0.0",
+            ]
+
+            [[package]]
+            name = "normal_dep"
+            version = "1.2.3"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            checksum = "76b5842e81eb9bbea19276a9dbbda22ac042532f390a67ab08b895617978abf3"
+
+            [[package]]
+            name = "semver"
+            version = "1.0.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            checksum = "76b5842e81eb9bbea19276a9dbbda22ac042532f390a67ab08b895617978abf3"
+
+            [[package]]
+            name = "semver"
+            version = "0.9.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            checksum = "76b5842e81eb9bbea19276a9dbbda22ac042532f390a67ab08b895617978abf3""#;
+        let deps = super::parse_dependencies(&lock_toml_buf.parse().unwrap());
+        assert_eq!(
+            deps,
+            [
+                ("foobar".to_owned(), "1.0.0".to_owned()),
+                ("normal_dep".to_owned(), "1.2.3".to_owned()),
+                ("semver".to_owned(), "0.9.0".to_owned()),
+                ("semver".to_owned(), "1.0.0".to_owned()),
+            ]
+        );
+        assert_eq!(
+            super::parse_lockfile_version(&lock_toml_buf.parse().unwrap()),
+            3
+        );
+
+        let sources = super::parse_dependency_sources(&lock_toml_buf.parse().unwrap());
+        assert_eq!(sources, ["path", "crates.io", "crates.io", "crates.io"]);
+
+        let deps = super::parse_dependencies(&"version = 3".parse().unwrap());
+        assert!(deps.is_empty());
+    }
 }
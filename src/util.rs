@@ -7,31 +7,32 @@
 ///
 /// The function takes a reference to an array of names and version numbers as
 /// serialized by `built` and returns an iterator over the unchanged names
-/// and parsed version numbers.
+/// and the parsed version numbers. Versions that are not valid semver are
+/// not dropped; an `Err`-value is yielded for them instead.
 ///
 /// ```
 /// pub mod build_info {
-///     pub const DEPENDENCIES: [(&'static str, &'static str); 1] = [("built", "0.1.0")];
+///     pub const DEPENDENCIES: [(&'static str, &'static str); 2] = [("built", "0.1.0"), ("foo", "bar")];
 /// }
 ///
 /// let deps = build_info::DEPENDENCIES;
 /// assert!(built::util::parse_versions(&deps)
 ///                      .any(|(name, ver)| name == "built" &&
-///                                         ver >= semver::Version::parse("0.1.0").unwrap()));
+///                                         ver.unwrap() >= semver::Version::parse("0.1.0").unwrap()));
+/// assert!(built::util::parse_versions(&deps)
+///                      .any(|(name, ver)| name == "foo" && ver.is_err()));
 /// ```
-///
-/// # Panics
-/// If a version can't be parsed by `semver::Version::parse()`. This should never
-/// happen with version strings provided by Cargo and `built`.
 #[cfg(feature = "semver")]
 pub fn parse_versions<'a, T>(
     name_and_versions: T,
-) -> impl Iterator<Item = (&'a str, semver::Version)>
+) -> impl Iterator<Item = (&'a str, Result<semver::Version, semver::Error>)>
 where
     T: IntoIterator<Item = &'a (&'a str, &'a str)>,
 {
-    fn parse_version<'a>(t: &'a (&'a str, &'a str)) -> (&'a str, semver::Version) {
-        (t.0, t.1.parse().unwrap())
+    fn parse_version<'a>(
+        t: &'a (&'a str, &'a str),
+    ) -> (&'a str, Result<semver::Version, semver::Error>) {
+        (t.0, t.1.parse())
     }
     name_and_versions.into_iter().map(parse_version)
 }
@@ -58,8 +59,198 @@ pub fn strptime(s: &str) -> chrono::DateTime<chrono::offset::Utc> {
         .with_timezone(&chrono::offset::Utc)
 }
 
+/// Describe how long ago a time-string as formatted by `built` was, relative
+/// to the current time.
+///
+/// The result is something like `"just now"`, `"5 minutes ago"` or
+/// `"3 days ago"`, suitable for e.g. `--version`-output. A time in the future
+/// is described as `"in the future"`.
+///
+/// ```
+/// pub mod build_info {
+///     pub const BUILT_TIME_UTC: &'static str = "Tue, 14 Feb 2017 05:21:41 GMT";
+/// }
+///
+/// assert!(built::util::time_ago(&build_info::BUILT_TIME_UTC).ends_with(" years ago"));
+/// ```
+///
+/// # Panics
+/// If the string can't be parsed. This should never happen with input provided
+/// by `built`.
+#[cfg(feature = "chrono")]
+#[must_use]
+pub fn time_ago(built_time: &str) -> String {
+    let age = chrono::offset::Utc::now().signed_duration_since(strptime(built_time));
+    fmt_age(age.num_seconds())
+}
+
+/// Formats an age given in seconds the way `time_ago()` does.
+#[cfg(feature = "chrono")]
+pub(crate) fn fmt_age(seconds: i64) -> String {
+    if seconds < 0 {
+        return "in the future".to_owned();
+    }
+    let (n, unit) = match seconds {
+        0..=59 => return "just now".to_owned(),
+        60..=3_599 => (seconds / 60, "minute"),
+        3_600..=86_399 => (seconds / 3_600, "hour"),
+        86_400..=2_591_999 => (seconds / 86_400, "day"),
+        2_592_000..=31_535_999 => (seconds / 2_592_000, "month"),
+        _ => (seconds / 31_536_000, "year"),
+    };
+    format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" })
+}
+
+/// Checks whether a feature is in a list of features as serialized by `built`.
+///
+/// `FEATURES` contains the names the way Cargo names the `CARGO_FEATURE_*`
+/// variables, uppercase and with `-` replaced by `_`, so the feature may be
+/// given either by that name or as declared in the manifest. The list is
+/// expected to be sorted, as `FEATURES` is.
+///
+/// ```
+/// pub mod build_info {
+///     pub const FEATURES: [&'static str; 2] = ["DEFAULT", "WAYLAND_CLIENT"];
+/// }
+///
+/// assert!(built::util::has_feature(&build_info::FEATURES, "wayland-client"));
+/// assert!(!built::util::has_feature(&build_info::FEATURES, "x11"));
+/// ```
+#[must_use]
+pub fn has_feature(features: &[&str], name: &str) -> bool {
+    let name = name.to_uppercase().replace('-', "_");
+    features.binary_search(&name.as_str()).is_ok()
+}
+
+/// A point in time as parsed by `parse_rfc822()`.
+///
+/// The fields are given in the timezone of the original string, which is
+/// `offset` seconds east of UTC; for `BUILT_TIME_UTC`, `offset` is always zero.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rfc822Time {
+    /// The year, e.g. `2017`.
+    pub year: i32,
+    /// The month, `1` to `12`.
+    pub month: u8,
+    /// The day of the month, `1` to `31`.
+    pub day: u8,
+    /// The hour, `0` to `23`.
+    pub hour: u8,
+    /// The minute, `0` to `59`.
+    pub minute: u8,
+    /// The second, `0` to `60` (to allow for leap seconds).
+    pub second: u8,
+    /// The offset of the timezone from UTC, in seconds.
+    pub offset: i32,
+}
+
+impl Rfc822Time {
+    /// The number of seconds since the Unix epoch.
+    ///
+    /// ```
+    /// let t = built::util::parse_rfc822("Fri, 14 Jul 2017 02:40:00 +0000").unwrap();
+    /// assert_eq!(t.unix_timestamp(), 1_500_000_000);
+    /// ```
+    #[must_use]
+    pub fn unix_timestamp(&self) -> i64 {
+        // Days since the epoch in the proleptic gregorian calendar
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+        days * 86_400
+            + i64::from(self.hour) * 3_600
+            + i64::from(self.minute) * 60
+            + i64::from(self.second)
+            - i64::from(self.offset)
+    }
+}
+
+/// Parse a time-string as formatted by `built`, without depending on `chrono`.
+///
+/// This is a lightweight alternative to `strptime()` for dates in the format of
+/// RFC822/RFC2822, like `BUILT_TIME_UTC`. The day of the week is optional and
+/// not checked; the timezone may be numeric (like `+0100`) or one of `GMT`,
+/// `UT`, `UTC` and `Z`. `None` is returned if the string can't be parsed.
+///
+/// ```
+/// pub mod build_info {
+///     pub const BUILT_TIME_UTC: &'static str = "Tue, 14 Feb 2017 05:21:41 GMT";
+/// }
+///
+/// let t = built::util::parse_rfc822(build_info::BUILT_TIME_UTC).unwrap();
+/// assert_eq!((t.year, t.month, t.day), (2017, 2, 14));
+/// assert_eq!((t.hour, t.minute, t.second), (5, 21, 41));
+/// ```
+#[must_use]
+pub fn parse_rfc822(s: &str) -> Option<Rfc822Time> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let s = match s.find(',') {
+        Some(idx) => &s[idx + 1..],
+        None => s,
+    };
+    let mut parts = s.split_whitespace();
+    let day = parts
+        .next()?
+        .parse()
+        .ok()
+        .filter(|d| (1..=31).contains(d))?;
+    let month = parts.next()?;
+    let month = MONTHS
+        .iter()
+        .position(|m| m.eq_ignore_ascii_case(month))
+        .map(|idx| idx as u8 + 1)?;
+    let year = parts.next()?.parse().ok()?;
+    let mut time = parts.next()?.split(':');
+    let hour = time.next()?.parse().ok().filter(|h| *h < 24)?;
+    let minute = time.next()?.parse().ok().filter(|m| *m < 60)?;
+    let second = match time.next() {
+        Some(second) => second.parse().ok().filter(|s| *s <= 60)?,
+        None => 0,
+    };
+    if time.next().is_some() {
+        return None;
+    }
+    let offset = match parts.next()? {
+        "GMT" | "UT" | "UTC" | "Z" => 0,
+        zone if zone.len() == 5 && (zone.starts_with('+') || zone.starts_with('-')) => {
+            if !zone[1..].bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let hours: i32 = zone.get(1..3)?.parse().ok().filter(|h| *h < 24)?;
+            let minutes: i32 = zone.get(3..)?.parse().ok().filter(|m| *m < 60)?;
+            let offset = hours * 3_600 + minutes * 60;
+            if zone.starts_with('-') {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Rfc822Time {
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        offset,
+    })
+}
+
 /// Retrieves the git-tag or hash describing the exact version and a boolean
-/// that indicates if the repository currently has dirty/staged files.
+/// that indicates if the repository currently has dirty/staged/untracked files.
 ///
 /// If a valid git-repo can't be discovered at or above the given path,
 /// `Ok(None)` is returned instead of an `Err`-value.
@@ -68,30 +259,83 @@ pub fn strptime(s: &str) -> chrono::DateTime<chrono::offset::Utc> {
 /// Errors from `git2` are returned if the repository does exists at all.
 #[cfg(feature = "git2")]
 pub fn get_repo_description(root: &std::path::Path) -> Result<Option<(String, bool)>, git2::Error> {
-    match git2::Repository::discover(root) {
-        Ok(repo) => {
-            let mut desc_opt = git2::DescribeOptions::new();
-            desc_opt.describe_tags().show_commit_oid_as_fallback(true);
-            let tag = repo
-                .describe(&desc_opt)
-                .and_then(|desc| desc.format(None))?;
-            let mut st_opt = git2::StatusOptions::new();
-            st_opt.include_ignored(false);
-            st_opt.include_untracked(false);
-            let dirty = repo
-                .statuses(Some(&mut st_opt))?
-                .iter()
-                .any(|status| !matches!(status.status(), git2::Status::CURRENT));
-            Ok(Some((tag, dirty)))
+    with_repo(root, repo_description)
+}
+
+/// See `get_repo_description()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_description(repo: &git2::Repository) -> Result<(String, bool), git2::Error> {
+    let mut desc_opt = git2::DescribeOptions::new();
+    desc_opt.describe_tags().show_commit_oid_as_fallback(true);
+    let tag = repo
+        .describe(&desc_opt)
+        .and_then(|desc| desc.format(None))?;
+    let mut st_opt = git2::StatusOptions::new();
+    st_opt.include_ignored(false);
+    st_opt.include_untracked(true);
+    let dirty = repo
+        .statuses(Some(&mut st_opt))?
+        .iter()
+        .any(|status| !matches!(status.status(), git2::Status::CURRENT));
+    Ok((tag, dirty))
+}
+
+/// Retrieves a description of HEAD like `git describe --tags --first-parent`.
+///
+/// `git2` can't restrict `describe` to the first-parent history, so the
+/// history is walked manually: Starting at HEAD, only the first parent of each
+/// commit is followed until a tagged commit is found. The result is the tag's
+/// name if HEAD itself is tagged, `<tag>-<n>-g<hash>` if the tag is `n`
+/// commits away or HEAD's abbreviated hash if no tag is found at all. If a
+/// commit has more than one tag, the name that sorts last is used.
+///
+/// If a valid git-repo can't be discovered at or above the given path,
+/// `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_description_first_parent(
+    root: &std::path::Path,
+) -> Result<Option<String>, git2::Error> {
+    with_repo(root, repo_description_first_parent)
+}
+
+/// See `get_repo_description_first_parent()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_description_first_parent(
+    repo: &git2::Repository,
+) -> Result<String, git2::Error> {
+    let mut tags = std::collections::HashMap::<git2::Oid, String>::new();
+    repo.tag_foreach(|oid, name| {
+        let name = String::from_utf8_lossy(name);
+        let name = name.strip_prefix("refs/tags/").unwrap_or(&name);
+        if let Ok(commit) = repo.find_object(oid, None).and_then(|o| o.peel_to_commit()) {
+            let entry = tags.entry(commit.id()).or_default();
+            if entry.as_str() < name {
+                *entry = name.to_owned();
+            }
         }
-        Err(ref e)
-            if e.class() == git2::ErrorClass::Repository
-                && e.code() == git2::ErrorCode::NotFound =>
-        {
-            Ok(None)
+        true
+    })?;
+
+    let head = repo.head()?.peel_to_commit()?;
+    let short_id = head.as_object().short_id()?;
+    let short_id = short_id.as_str().unwrap_or_default();
+    let mut commit = Some(head);
+    let mut depth = 0;
+    while let Some(c) = commit {
+        if let Some(tag) = tags.get(&c.id()) {
+            return Ok(if depth == 0 {
+                tag.clone()
+            } else {
+                format!("{}-{}-g{}", tag, depth, short_id)
+            });
         }
-        Err(e) => Err(e),
+        commit = c.parents().next();
+        depth += 1;
     }
+    Ok(short_id.to_owned())
 }
 
 /// Retrieves the branch name and hash of HEAD.
@@ -108,26 +352,430 @@ pub fn get_repo_description(root: &std::path::Path) -> Result<Option<(String, bo
 pub fn get_repo_head(
     root: &std::path::Path,
 ) -> Result<Option<(Option<String>, String)>, git2::Error> {
-    match git2::Repository::discover(root) {
-        Ok(repo) => {
-            // Supposed to be the reference pointed to by HEAD, but it's HEAD
-            // itself, if detached
-            let head_ref = repo.head()?;
-            let branch = {
-                // Check whether `head` is realy the pointed to reference and
-                // not HEAD itself.
-                if !repo.head_detached()? {
-                    head_ref.name()
-                } else {
-                    None
-                }
-            };
-            let commit = head_ref.peel_to_commit()?.id();
-            Ok(Some((
-                branch.map(ToString::to_string),
-                format!("{}", commit),
-            )))
+    with_repo(root, repo_head)
+}
+
+/// See `get_repo_head()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_head(repo: &git2::Repository) -> Result<(Option<String>, String), git2::Error> {
+    // Supposed to be the reference pointed to by HEAD, but it's HEAD
+    // itself, if detached
+    let head_ref = repo.head()?;
+    let branch = {
+        // Check whether `head` is realy the pointed to reference and
+        // not HEAD itself, and that it's a branch below `refs/heads/`.
+        if !repo.head_detached()? && head_ref.is_branch() {
+            head_ref.name()
+        } else {
+            None
+        }
+    };
+    let commit = head_ref.peel_to_commit()?.id();
+    Ok((branch.map(ToString::to_string), format!("{}", commit)))
+}
+
+/// Retrieves the abbreviated hash of HEAD.
+///
+/// The hash is abbreviated like `git rev-parse --short`, which is at least
+/// seven characters but longer if required to be unambiguous.
+///
+/// If a valid git-repo can't be discovered at or above the given path,
+/// `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_head_short_hash(root: &std::path::Path) -> Result<Option<String>, git2::Error> {
+    with_repo(root, repo_head_short_hash).map(Option::flatten)
+}
+
+/// See `get_repo_head_short_hash()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_head_short_hash(repo: &git2::Repository) -> Result<Option<String>, git2::Error> {
+    let commit = repo.head()?.peel_to_commit()?;
+    let short_id = commit.as_object().short_id()?;
+    Ok(short_id.as_str().map(ToString::to_string))
+}
+
+/// Retrieves the hashes of the parents of the commit HEAD points to.
+///
+/// There are two or more parents for a merge-commit, one for a regular commit
+/// and none for a root-commit.
+///
+/// If a valid git-repo can't be discovered at or above the given path,
+/// `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_commit_parents(root: &std::path::Path) -> Result<Option<Vec<String>>, git2::Error> {
+    with_repo(root, repo_commit_parents)
+}
+
+/// See `get_repo_commit_parents()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_commit_parents(repo: &git2::Repository) -> Result<Vec<String>, git2::Error> {
+    let commit = repo.head()?.peel_to_commit()?;
+    Ok(commit.parent_ids().map(|oid| oid.to_string()).collect())
+}
+
+/// Retrieves the name of the default branch of the `origin`-remote.
+///
+/// The default branch is taken from the symbolic reference
+/// `refs/remotes/origin/HEAD`, e.g. `main` or `master`.
+///
+/// If a valid git-repo can't be discovered at or above the given path, or
+/// if `origin/HEAD` is not set, `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_default_branch(root: &std::path::Path) -> Result<Option<String>, git2::Error> {
+    with_repo(root, repo_default_branch).map(Option::flatten)
+}
+
+/// See `get_repo_default_branch()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_default_branch(repo: &git2::Repository) -> Result<Option<String>, git2::Error> {
+    let origin_head = match repo.find_reference("refs/remotes/origin/HEAD") {
+        Ok(r) => r,
+        Err(ref e) if e.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    Ok(origin_head
+        .symbolic_target()
+        .and_then(|target| target.strip_prefix("refs/remotes/origin/"))
+        .map(ToString::to_string))
+}
+
+/// Retrieves the names and urls of the repository's remotes, sorted by name.
+///
+/// Remotes whose name or url is not valid UTF-8 are skipped.
+///
+/// If a valid git-repo can't be discovered at or above the given path,
+/// `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_remotes(
+    root: &std::path::Path,
+) -> Result<Option<Vec<(String, String)>>, git2::Error> {
+    with_repo(root, repo_remotes)
+}
+
+/// See `get_repo_remotes()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_remotes(repo: &git2::Repository) -> Result<Vec<(String, String)>, git2::Error> {
+    let mut remotes = Vec::new();
+    for name in repo.remotes()?.iter().flatten() {
+        let remote = repo.find_remote(name)?;
+        if let Some(url) = remote.url() {
+            remotes.push((name.to_owned(), url.to_owned()));
         }
+    }
+    remotes.sort_unstable();
+    Ok(remotes)
+}
+
+/// Retrieves whether the repository is bare and whether it is a linked worktree.
+///
+/// The returned value is a tuple of `is_bare` and `is_worktree`. These help
+/// explain why information depending on the working directory (like the
+/// dirty-state) may be missing.
+///
+/// If a valid git-repo can't be discovered at or above the given path,
+/// `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_kind(root: &std::path::Path) -> Result<Option<(bool, bool)>, git2::Error> {
+    with_repo(root, repo_kind)
+}
+
+/// See `get_repo_kind()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_kind(repo: &git2::Repository) -> Result<(bool, bool), git2::Error> {
+    Ok((repo.is_bare(), repo.is_worktree()))
+}
+
+/// Retrieves whether HEAD is detached, i.e. points to a commit instead of a branch.
+///
+/// If a valid git-repo can't be discovered at or above the given path,
+/// `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_head_detached(root: &std::path::Path) -> Result<Option<bool>, git2::Error> {
+    with_repo(root, git2::Repository::head_detached)
+}
+
+/// Retrieves whether HEAD is exactly on a tag.
+///
+/// Both annotated and lightweight tags are considered.
+///
+/// If a valid git-repo can't be discovered at or above the given path,
+/// `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_tagged(root: &std::path::Path) -> Result<Option<bool>, git2::Error> {
+    with_repo(root, repo_tagged)
+}
+
+/// See `get_repo_tagged()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_tagged(repo: &git2::Repository) -> Result<bool, git2::Error> {
+    let head = repo.head()?.peel_to_commit()?.id();
+    let mut tagged = false;
+    repo.tag_foreach(|oid, _| {
+        tagged |= repo
+            .find_object(oid, None)
+            .and_then(|obj| obj.peel_to_commit())
+            .is_ok_and(|commit| commit.id() == head);
+        true
+    })?;
+    Ok(tagged)
+}
+
+/// Retrieves the names of all tags pointing at HEAD, sorted by name.
+///
+/// Both annotated and lightweight tags are considered; the list is empty if
+/// HEAD is not tagged.
+///
+/// If a valid git-repo can't be discovered at or above the given path,
+/// `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_head_tags(root: &std::path::Path) -> Result<Option<Vec<String>>, git2::Error> {
+    with_repo(root, repo_head_tags)
+}
+
+/// See `get_repo_head_tags()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_head_tags(repo: &git2::Repository) -> Result<Vec<String>, git2::Error> {
+    let head = repo.head()?.peel_to_commit()?.id();
+    let mut tags = Vec::new();
+    for name in repo.tag_names(None)?.iter().flatten() {
+        let target = repo
+            .find_reference(&format!("refs/tags/{}", name))
+            .and_then(|r| r.peel_to_commit());
+        if target.is_ok_and(|commit| commit.id() == head) {
+            tags.push(name.to_owned());
+        }
+    }
+    tags.sort();
+    Ok(tags)
+}
+
+/// Retrieves the number of commits since the closest tag reachable from HEAD.
+///
+/// Like `git describe`, the commits reachable from HEAD but not from the tag
+/// are counted; if HEAD is exactly on a tag, this is zero. Both annotated and
+/// lightweight tags are considered.
+///
+/// If a valid git-repo can't be discovered at or above the given path, or if
+/// no tag is reachable from HEAD, `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_commits_since_tag(root: &std::path::Path) -> Result<Option<u32>, git2::Error> {
+    with_repo(root, repo_commits_since_tag).map(Option::flatten)
+}
+
+/// See `get_repo_commits_since_tag()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_commits_since_tag(repo: &git2::Repository) -> Result<Option<u32>, git2::Error> {
+    let mut tagged = std::collections::HashSet::new();
+    repo.tag_foreach(|oid, _| {
+        if let Ok(commit) = repo
+            .find_object(oid, None)
+            .and_then(|obj| obj.peel_to_commit())
+        {
+            tagged.insert(commit.id());
+        }
+        true
+    })?;
+    if tagged.is_empty() {
+        return Ok(None);
+    }
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+    revwalk.push_head()?;
+    let mut closest = None;
+    for oid in revwalk {
+        let oid = oid?;
+        if tagged.contains(&oid) {
+            closest = Some(oid);
+            break;
+        }
+    }
+    let closest = match closest {
+        Some(closest) => closest,
+        None => return Ok(None),
+    };
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push_head()?;
+    revwalk.hide(closest)?;
+    let mut count = 0;
+    for oid in revwalk {
+        oid?;
+        count += 1;
+    }
+    Ok(Some(count))
+}
+
+/// Retrieves whether the commit HEAD points to carries a signature.
+///
+/// Only the presence of a (GPG, SSH, ...) signature is detected; whether the
+/// signature is actually valid is *not* checked.
+///
+/// If a valid git-repo can't be discovered at or above the given path,
+/// `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_commit_signed(root: &std::path::Path) -> Result<Option<bool>, git2::Error> {
+    with_repo(root, repo_commit_signed)
+}
+
+/// See `get_repo_commit_signed()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_commit_signed(repo: &git2::Repository) -> Result<bool, git2::Error> {
+    let commit = repo.head()?.peel_to_commit()?.id();
+    match repo.extract_signature(&commit, None) {
+        Ok(_) => Ok(true),
+        Err(ref e) if e.code() == git2::ErrorCode::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Retrieves the path of `root` relative to the repository's working directory.
+///
+/// The components of the returned path are separated by `/` on all platforms;
+/// the path is empty if `root` is the working directory itself.
+///
+/// If a valid git-repo can't be discovered at or above the given path, or
+/// if the repository has no working directory, `Ok(None)` is returned instead
+/// of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_relative_path(root: &std::path::Path) -> Result<Option<String>, git2::Error> {
+    with_repo(root, |repo| repo_relative_path(repo, root)).map(Option::flatten)
+}
+
+/// See `get_repo_relative_path()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_relative_path(
+    repo: &git2::Repository,
+    root: &std::path::Path,
+) -> Result<Option<String>, git2::Error> {
+    fn canonicalize(p: &std::path::Path) -> Result<std::path::PathBuf, git2::Error> {
+        p.canonicalize()
+            .map_err(|e| git2::Error::from_str(&e.to_string()))
+    }
+
+    let workdir = match repo.workdir() {
+        Some(workdir) => canonicalize(workdir)?,
+        None => return Ok(None),
+    };
+    let root = canonicalize(root)?;
+    Ok(root.strip_prefix(workdir).ok().map(|rel| {
+        rel.components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }))
+}
+
+/// Retrieves a summary of the working directory's status, like `git status --porcelain`.
+///
+/// Each line consists of the two-letter status code and the path of the
+/// file, e.g. ` M src/lib.rs` for a modified or `?? new.txt` for an untracked
+/// file. Ignored files are not included. The summary is empty if the
+/// working directory is clean.
+///
+/// If a valid git-repo can't be discovered at or above the given path,
+/// `Ok(None)` is returned instead of an `Err`-value.
+///
+/// # Errors
+/// Errors from `git2` are returned if the repository does exists at all.
+#[cfg(feature = "git2")]
+pub fn get_repo_status(root: &std::path::Path) -> Result<Option<String>, git2::Error> {
+    with_repo(root, repo_status)
+}
+
+/// See `get_repo_status()`.
+#[cfg(feature = "git2")]
+pub(crate) fn repo_status(repo: &git2::Repository) -> Result<String, git2::Error> {
+    let mut st_opt = git2::StatusOptions::new();
+    st_opt.include_ignored(false);
+    st_opt.include_untracked(true);
+    let lines = repo
+        .statuses(Some(&mut st_opt))?
+        .iter()
+        .map(|entry| {
+            let path = String::from_utf8_lossy(entry.path_bytes());
+            format!("{} {}", porcelain_code(entry.status()), path)
+        })
+        .collect::<Vec<_>>();
+    Ok(lines.join("\n"))
+}
+
+/// The two-letter code of `git status --porcelain`.
+#[cfg(feature = "git2")]
+fn porcelain_code(status: git2::Status) -> String {
+    if status.is_conflicted() {
+        return "UU".to_owned();
+    }
+    if status.is_wt_new() {
+        return "??".to_owned();
+    }
+    let index = if status.is_index_new() {
+        'A'
+    } else if status.is_index_modified() {
+        'M'
+    } else if status.is_index_deleted() {
+        'D'
+    } else if status.is_index_renamed() {
+        'R'
+    } else if status.is_index_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+    let worktree = if status.is_wt_modified() {
+        'M'
+    } else if status.is_wt_deleted() {
+        'D'
+    } else if status.is_wt_renamed() {
+        'R'
+    } else if status.is_wt_typechange() {
+        'T'
+    } else {
+        ' '
+    };
+    format!("{}{}", index, worktree)
+}
+
+/// Discovers the git-repo at or above the given path, `Ok(None)` if there is none.
+#[cfg(feature = "git2")]
+pub(crate) fn discover_repo(
+    root: &std::path::Path,
+) -> Result<Option<git2::Repository>, git2::Error> {
+    match git2::Repository::discover(root) {
+        Ok(repo) => Ok(Some(repo)),
         Err(ref e)
             if e.class() == git2::ErrorClass::Repository
                 && e.code() == git2::ErrorCode::NotFound =>
@@ -138,6 +786,56 @@ pub fn get_repo_head(
     }
 }
 
+/// Runs `f` on the git-repo discovered at or above `root`, `Ok(None)` if there is none.
+#[cfg(feature = "git2")]
+fn with_repo<T, F>(root: &std::path::Path, f: F) -> Result<Option<T>, git2::Error>
+where
+    F: FnOnce(&git2::Repository) -> Result<T, git2::Error>,
+{
+    discover_repo(root)?.map(|repo| f(&repo)).transpose()
+}
+
+/// Retrieves a description of the Mercurial-repository at or above the given path.
+///
+/// The description is the first tag of the working directory's parent other
+/// than `tip`, and the short changeset id otherwise; a `+` is appended to the
+/// id by `hg` if the working directory has uncommitted changes.
+///
+/// This shells out to `hg id -i -t`. If no `.hg`-directory is found at or above
+/// the given path, or if `hg` is not installed, `Ok(None)` is returned instead of
+/// an `Err`-value.
+///
+/// # Errors
+/// Errors are returned if `hg` could not be executed for other reasons.
+#[cfg(feature = "hg")]
+pub fn get_hg_description(root: &std::path::Path) -> std::io::Result<Option<String>> {
+    if !root.ancestors().any(|p| p.join(".hg").is_dir()) {
+        return Ok(None);
+    }
+    let output = match std::process::Command::new("hg")
+        .args(["id", "-i", "-t"])
+        .env("HGPLAIN", "1")
+        .current_dir(root)
+        .output()
+    {
+        Ok(output) => output,
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(parse_hg_id(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses the output of `hg id -i -t`, preferring a tag over the changeset id.
+#[cfg(feature = "hg")]
+pub(crate) fn parse_hg_id(s: &str) -> Option<String> {
+    let mut words = s.split_whitespace();
+    let id = words.next()?;
+    Some(words.find(|&tag| tag != "tip").unwrap_or(id).to_owned())
+}
+
 /// Detect execution on various Continiuous Integration platforms.
 ///
 /// CI-platforms are detected by the presence of known environment variables.
@@ -149,5 +847,351 @@ pub fn get_repo_head(
 /// (e.g. `TASK_ID`), this function may have false positives.
 #[must_use]
 pub fn detect_ci() -> Option<super::CIPlatform> {
-    super::CIPlatform::detect_from_envmap(&super::get_environment())
+    super::CIPlatform::detect()
+}
+
+/// Composes the web-url of a CI-build from the environment variables of the CI-platform.
+///
+/// `platform` is the name of the platform as given by `CI_PLATFORM` (or
+/// `"GitHub Actions"`), `envlike` are the environment variables captured during
+/// the build, e.g. via `Options::set_rust_env()` or the build-script itself.
+/// `None` is returned for unknown platforms or if a variable is missing.
+///
+/// ```
+/// let vars = [
+///     ("GITHUB_SERVER_URL", "https://github.com"),
+///     ("GITHUB_REPOSITORY", "lukaslueg/built"),
+///     ("GITHUB_RUN_ID", "42"),
+/// ];
+/// assert_eq!(
+///     built::util::ci_build_url("GitHub Actions", &vars).as_deref(),
+///     Some("https://github.com/lukaslueg/built/actions/runs/42")
+/// );
+/// ```
+#[must_use]
+pub fn ci_build_url(platform: &str, envlike: &[(&str, &str)]) -> Option<String> {
+    let var = |name: &str| {
+        envlike
+            .iter()
+            .rev()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| *v)
+            .filter(|v| !v.is_empty())
+    };
+    match platform {
+        "GitHub Actions" => Some(format!(
+            "{}/{}/actions/runs/{}",
+            var("GITHUB_SERVER_URL")
+                .unwrap_or("https://github.com")
+                .trim_end_matches('/'),
+            var("GITHUB_REPOSITORY")?,
+            var("GITHUB_RUN_ID")?
+        )),
+        "GitLab" => var("CI_JOB_URL")
+            .or_else(|| var("CI_PIPELINE_URL"))
+            .map(ToString::to_string),
+        "Travis CI" => var("TRAVIS_BUILD_WEB_URL").map(ToString::to_string),
+        "CircleCI" => var("CIRCLE_BUILD_URL").map(ToString::to_string),
+        "AppVeyor" => Some(format!(
+            "{}/project/{}/{}/builds/{}",
+            var("APPVEYOR_URL")?.trim_end_matches('/'),
+            var("APPVEYOR_ACCOUNT_NAME")?,
+            var("APPVEYOR_PROJECT_SLUG")?,
+            var("APPVEYOR_BUILD_ID")?
+        )),
+        "Jenkins" | "Hudson" => var("BUILD_URL").map(ToString::to_string),
+        "Buildkite" => var("BUILDKITE_BUILD_URL").map(ToString::to_string),
+        "Drone" => var("DRONE_BUILD_LINK").map(ToString::to_string),
+        "Azure Pipelines" => Some(format!(
+            "{}/{}/_build/results?buildId={}",
+            var("SYSTEM_TEAMFOUNDATIONCOLLECTIONURI")?.trim_end_matches('/'),
+            var("SYSTEM_TEAMPROJECT")?,
+            var("BUILD_BUILDID")?
+        )),
+        "AWS CodeBuild" => var("CODEBUILD_BUILD_URL").map(ToString::to_string),
+        "Cirrus CI" => Some(format!(
+            "https://cirrus-ci.com/build/{}",
+            var("CIRRUS_BUILD_ID")?
+        )),
+        _ => None,
+    }
+}
+
+/// A value of a JSON-document, as parsed by [`parse_built_json`][pbj].
+///
+/// [pbj]: fn.parse_built_json.html
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuildInfoValue {
+    /// `null`
+    Null,
+    /// `true` or `false`
+    Bool(bool),
+    /// A number
+    Number(f64),
+    /// A string
+    String(String),
+    /// An array of values
+    Array(Vec<BuildInfoValue>),
+    /// A nested object
+    Object(BuildInfoMap),
+}
+
+/// The build-information parsed by [`parse_built_json`][pbj], by name.
+///
+/// [pbj]: fn.parse_built_json.html
+pub type BuildInfoMap = std::collections::BTreeMap<String, BuildInfoValue>;
+
+/// The error returned by [`parse_built_json`][pbj] for malformed JSON.
+///
+/// [pbj]: fn.parse_built_json.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonError {
+    /// The byte-offset into the input where the error was detected.
+    pub offset: usize,
+    /// What went wrong.
+    pub message: &'static str,
+}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// Parses build-information given as a JSON-object.
+///
+/// This is a small, dependency-free parser, which allows tools to read
+/// build-information without having to rely on `serde`. The top-level value
+/// has to be an object, which is returned as a map by the name of each value.
+///
+/// ```
+/// use built::util::{parse_built_json, BuildInfoValue};
+///
+/// let info = parse_built_json(r#"{"PKG_VERSION": "1.2.3", "DEBUG": true}"#).unwrap();
+/// assert_eq!(info["PKG_VERSION"], BuildInfoValue::String("1.2.3".to_owned()));
+/// assert_eq!(info["DEBUG"], BuildInfoValue::Bool(true));
+/// ```
+///
+/// # Errors
+/// A `JsonError` is returned if `s` is not valid JSON or not an object, or if
+/// objects and arrays are nested more than 128 levels deep.
+pub fn parse_built_json(s: &str) -> Result<BuildInfoMap, JsonError> {
+    let mut parser = JsonParser {
+        s,
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    if parser.peek() != Some(b'{') {
+        return Err(parser.error("Expected an object"));
+    }
+    let map = match parser.parse_value()? {
+        BuildInfoValue::Object(map) => map,
+        _ => unreachable!(),
+    };
+    parser.skip_whitespace();
+    if parser.pos != s.len() {
+        return Err(parser.error("Trailing characters"));
+    }
+    Ok(map)
+}
+
+/// The maximum nesting of objects and arrays, which keeps the recursive
+/// parser from overflowing the stack.
+const MAX_JSON_DEPTH: usize = 128;
+
+struct JsonParser<'a> {
+    s: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, message: &'static str) -> JsonError {
+        JsonError {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.s.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8, message: &'static str) -> Result<(), JsonError> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<BuildInfoValue, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{' | b'[') => {
+                if self.depth == MAX_JSON_DEPTH {
+                    return Err(self.error("Nesting too deep"));
+                }
+                self.depth += 1;
+                let value = if self.peek() == Some(b'{') {
+                    self.parse_object().map(BuildInfoValue::Object)
+                } else {
+                    self.parse_array().map(BuildInfoValue::Array)
+                };
+                self.depth -= 1;
+                value
+            }
+            Some(b'"') => self.parse_string().map(BuildInfoValue::String),
+            Some(b'-' | b'0'..=b'9') => self.parse_number().map(BuildInfoValue::Number),
+            Some(_) => {
+                for (literal, value) in &[
+                    ("null", BuildInfoValue::Null),
+                    ("true", BuildInfoValue::Bool(true)),
+                    ("false", BuildInfoValue::Bool(false)),
+                ] {
+                    if self.s[self.pos..].starts_with(literal) {
+                        self.pos += literal.len();
+                        return Ok(value.clone());
+                    }
+                }
+                Err(self.error("Expected a value"))
+            }
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<BuildInfoMap, JsonError> {
+        let mut map = BuildInfoMap::new();
+        self.expect(b'{', "Expected an object")?;
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(map);
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("Expected a string as key"));
+            }
+            let key = self.parse_string()?;
+            self.expect(b':', "Expected ':' after key")?;
+            let value = self.parse_value()?;
+            map.insert(key, value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(map);
+                }
+                _ => return Err(self.error("Expected ',' or '}' in object")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Vec<BuildInfoValue>, JsonError> {
+        let mut values = Vec::new();
+        self.expect(b'[', "Expected an array")?;
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(values);
+        }
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(values);
+                }
+                _ => return Err(self.error("Expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<f64, JsonError> {
+        let start = self.pos;
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.s[start..self.pos].parse().map_err(|_| JsonError {
+            offset: start,
+            message: "Invalid number",
+        })
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let hex = self
+            .s
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.error("Unexpected end of input"))?;
+        let code =
+            u32::from_str_radix(hex, 16).map_err(|_| self.error("Invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        let mut out = String::new();
+        self.expect(b'"', "Expected a string")?;
+        loop {
+            let c = match self.s[self.pos..].chars().next() {
+                Some(c) => c,
+                None => return Err(self.error("Unterminated string")),
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            let mut code = self.parse_hex4()?;
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.s[self.pos..].starts_with("\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.parse_hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(self.error("Invalid unicode escape"));
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            out.push(
+                                std::char::from_u32(code)
+                                    .ok_or_else(|| self.error("Invalid unicode escape"))?,
+                            );
+                            continue;
+                        }
+                        _ => return Err(self.error("Invalid escape sequence")),
+                    };
+                    self.pos += 1;
+                    out.push(escaped);
+                }
+                c if c < ' ' => return Err(self.error("Control character in string")),
+                c => out.push(c),
+            }
+        }
+    }
 }
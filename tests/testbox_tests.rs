@@ -63,7 +63,7 @@ fn main() {
     /// Hold on to the tempdir, it will be removed when dropped!
     fn create(self) -> io::Result<tempfile::TempDir> {
         fs::DirBuilder::new()
-            .create(&self.root.path().join("src"))
+            .create(self.root.path().join("src"))
             .unwrap();
         for (name, content) in self.files {
             let fname = self.root.path().join(name);
@@ -80,7 +80,7 @@ fn main() {
 
     fn run(root: &std::path::Path) {
         let cargo_result = process::Command::new("cargo")
-            .current_dir(&root)
+            .current_dir(root)
             .arg("run")
             .output()
             .expect("cargo failed");
@@ -141,6 +141,7 @@ description = \"xobtset\"
 homepage = \"localhost\"
 repository = \"https://dev.example.com/sources/testbox/\"
 license = \"MIT\"
+rust-version = \"1.56\"
 
 [dependencies]
 built = {{ path = {:?}, features=[\"git2\", \"chrono\", \"semver\"] }}
@@ -168,10 +169,24 @@ fn main() {
     env::set_var("CONTINUOUS_INTEGRATION", "1");
 
     let mut options = built::Options::default();
-    options.set_dependencies(true);
+    options
+        .set_dependencies(true)
+        .set_stale_check(true)
+        .set_source_hash(true)
+        .set_endianness_enum(true)
+        .set_json(true)
+        .set_const_fn(true)
+        .set_ext_trait(true)
+        .set_pkg_version_fn(true)
+        .register_generated_asset("logo", "assets/logo.png");
     let src = env::var("CARGO_MANIFEST_DIR").unwrap();
     let dst = path::Path::new(&env::var("OUT_DIR").unwrap()).join("built.rs");
     built::write_built_file_with_opts(&options, src.as_ref(), &dst).unwrap();
+
+    // The private module defines its own `pkg_version()`
+    options.set_public(false).set_pkg_version_fn(false);
+    let dst = path::Path::new(&env::var("OUT_DIR").unwrap()).join("built_private.rs");
+    built::write_built_file_with_opts(&options, src.as_ref(), &dst).unwrap();
 }"#,
     );
 
@@ -184,6 +199,17 @@ mod built_info {
     include!(concat!(env!("OUT_DIR"), "/built.rs"));
 }
 
+static INFO: built_info::BuiltInfo = built_info::built_info();
+
+#[deny(missing_docs, warnings)]
+mod private_info {
+    include!(concat!(env!("OUT_DIR"), "/built_private.rs"));
+
+    pub fn pkg_version() -> &'static str {
+        built_info().pkg_version
+    }
+}
+
 fn main() {
     assert_eq!(built_info::GIT_VERSION, None);
     assert_eq!(built_info::GIT_DIRTY, None);
@@ -201,6 +227,8 @@ fn main() {
     assert_eq!(built_info::PKG_HOMEPAGE, "localhost");
     assert_eq!(built_info::PKG_LICENSE, "MIT");
     assert_eq!(built_info::PKG_REPOSITORY, "https://dev.example.com/sources/testbox/");
+    assert_eq!(built_info::PKG_RUST_VERSION, "1.56");
+    assert_eq!(built_info::pkg_version(), built::semver::Version::parse(built_info::PKG_VERSION).unwrap());
     assert!(built_info::NUM_JOBS > 0);
     assert!(built_info::OPT_LEVEL == "0");
     assert!(built_info::DEBUG);
@@ -209,9 +237,25 @@ fn main() {
                ["DEFAULT", "MEGAAWESOME", "SUPERAWESOME"]);
     assert_eq!(built_info::FEATURES_STR,
                "DEFAULT, MEGAAWESOME, SUPERAWESOME");
+    assert!(built_info::NON_DEFAULT_FEATURES.is_empty());
+    assert_eq!(format!("{:?}", built_info::ENDIANNESS).to_lowercase(),
+               built_info::CFG_ENDIAN);
+    assert_eq!(built_info::GENERATED_ASSETS, [("logo", "assets/logo.png")]);
+    assert!(built_info::generated_asset("logo").unwrap().ends_with("/assets/logo.png"));
+    assert_eq!(built_info::generated_asset("icon"), None);
+    {
+        use built_info::BuiltInfoExt;
+        assert_eq!(built_info::BuildInfo.pkg_version(), built_info::PKG_VERSION);
+    }
+    let json = built::util::parse_built_json(built_info::BUILT_JSON).unwrap();
+    assert_eq!(json["PKG_VERSION"],
+               built::util::BuildInfoValue::String(built_info::PKG_VERSION.to_owned()));
     assert_ne!(built_info::RUSTC_VERSION, "");
     assert_ne!(built_info::RUSTDOC_VERSION, "");
     assert_ne!(built_info::DEPENDENCIES_STR, "");
+    assert_eq!(built_info::DEPENDENCIES_COUNT, built_info::DEPENDENCIES.len());
+    assert_eq!(built_info::FEATURES_COUNT, 3);
+    assert_eq!(built_info::SOURCE_TREE_HASH.len(), 16);
     assert_ne!(built_info::HOST, "");
     assert_ne!(built_info::TARGET, "");
     assert_ne!(built_info::RUSTC, "");
@@ -220,14 +264,21 @@ fn main() {
     assert_ne!(built_info::CFG_ENDIAN, "");
     assert_ne!(built_info::CFG_FAMILY, "");
     assert_ne!(built_info::CFG_OS, "");
-    assert_ne!(built_info::CFG_POINTER_WIDTH, "");
+    assert!(built_info::CFG_POINTER_WIDTH >= 16);
+    assert_ne!(built_info::CFG_TARGET_VENDOR, "");
     // For CFG_ENV, empty string is a possible value.
     let _: &'static str = built_info::CFG_ENV;
 
     assert!(built::util::parse_versions(built_info::DEPENDENCIES.iter())
-        .any(|(name, ver)| name == "toml" && ver >= built::semver::Version::parse("0.1.0").unwrap()));
+        .any(|(name, ver)| name == "toml" && ver.unwrap() >= built::semver::Version::parse("0.1.0").unwrap()));
 
     assert!((built::chrono::offset::Utc::now() - built::util::strptime(built_info::BUILT_TIME_UTC)).num_days() <= 1);
+    assert_eq!(built::util::strptime(built_info::BUILT_TIME_UTC).timestamp(), built_info::BUILT_TIME_UNIX);
+    assert!(!built_info::warn_if_stale(1));
+
+    assert_eq!(INFO.pkg_version, built_info::PKG_VERSION);
+    assert_eq!(INFO.features, built_info::FEATURES);
+    assert_eq!(private_info::pkg_version(), built_info::PKG_VERSION);
 }"#,
     );
     p.create_and_run();
@@ -249,15 +300,15 @@ fn main() {
 }
 "#,
     );
+    p.add_file(".gitignore", "target\nCargo.lock\n");
     let repo = p.init_git();
     let root = p.create().expect("Creating the project failed");
 
     let sig = git2::Signature::now("foo", "bar").unwrap();
 
     let mut idx = repo.index().unwrap();
-    for p in &["src/main.rs", "build.rs"] {
-        idx.add_path(path::Path::new(p)).unwrap();
-    }
+    idx.add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
     idx.write().unwrap();
     repo.commit(
         Some("HEAD"),
@@ -273,7 +324,7 @@ fn main() {
     let mut f = std::fs::OpenOptions::new()
         .write(true)
         .truncate(true)
-        .open(&root.path().join("src/main.rs"))
+        .open(root.path().join("src/main.rs"))
         .unwrap();
     f.write_all(
         r#"
@@ -344,7 +395,7 @@ fn main() {}
 
     let root = p.create().expect("Creating the project failed");
     let cargo_result = process::Command::new("cargo")
-        .current_dir(&root)
+        .current_dir(root.path())
         .arg("run")
         .env("RUSTC", &rustc_exe)
         .output()